@@ -2,10 +2,24 @@ pub mod add;
 pub mod branch;
 pub mod checkout;
 pub mod commit;
+pub mod config;
 pub mod dec_object;
 pub mod diff;
+pub mod fsck;
+pub mod gc;
 pub mod init;
+pub mod log;
 pub mod ls_files;
 pub mod merge;
+pub mod merge_base;
+pub mod mv;
+pub mod reflog;
+pub mod repack;
+pub mod reset;
+pub mod restore;
+pub mod rm;
+pub mod sequencer;
+pub mod stash;
 pub mod status;
+pub mod tag;
 pub mod write_tree;
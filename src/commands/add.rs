@@ -1,13 +1,13 @@
 use crate::commands::status::parse_gitignore;
 use crate::objects::blob;
 use crate::others::file_altering;
-use crate::others::index::{Index, ObjectInfo};
+use crate::others::index::{self, Index, ObjectInfo};
 use anyhow::Result;
-use std::fs::read_to_string;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 pub fn read_file_content(file_name: &str) -> Result<Vec<u8>> {
-    let content = read_to_string(file_name)?;
-    Ok(content.into_bytes())
+    let content = fs::read(file_name)?;
+    Ok(content)
 }
 
 pub fn verifcation(obj: &ObjectInfo) -> Result<usize> {
@@ -16,6 +16,9 @@ pub fn verifcation(obj: &ObjectInfo) -> Result<usize> {
     let mut flag = false;
     for line in index_content.lines() {
         line_number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
         let parsed_obj = ObjectInfo::from_pretty_print(line)?;
 
         if parsed_obj.path == obj.path {
@@ -23,7 +26,10 @@ pub fn verifcation(obj: &ObjectInfo) -> Result<usize> {
                 // we can add freely the obj
                 return Ok(1); // 1 if it does not exist
             }
-            if parsed_obj.ctime != obj.ctime || parsed_obj.mtime != obj.mtime {
+            if parsed_obj.hash != obj.hash
+                || parsed_obj.ctime != obj.ctime
+                || parsed_obj.mtime != obj.mtime
+            {
                 // i need to delete the line from the file index
                 return Ok(line_number + 150);
             }
@@ -43,7 +49,19 @@ pub fn add_file(file_name: &str) -> Result<()> {
     let content = read_file_content(file_name)?;
     let blobs = blob::Blob::new(content);
     blobs.create_blob()?;
-    let path = PathBuf::from(file_name);
+    let path = index::normalize_path(Path::new(file_name));
+
+    // adding a conflicted file marks it as resolved, its stages are dropped
+    let mut current = Index::load()?;
+    if current
+        .obj
+        .iter()
+        .any(|obj| obj.path == path && obj.stage != 0)
+    {
+        current.obj.retain(|obj| obj.path != path);
+        current.save_index_file_truncate()?;
+    }
+
     let mut index = Index::new();
     let obj = ObjectInfo::new("blob", &path, &blobs.get_hash())?;
 
@@ -1,14 +1,133 @@
+use crate::commands::{checkout, merge_base};
+use crate::others::{file_altering, reflog, refs, revision};
+use anyhow::anyhow;
 use anyhow::Result;
-use std::fs;
-use std::io::Write;
-use std::path::Path;
-pub fn branch_command(msg: &str) -> Result<()> {
-    let current_branch = fs::read_to_string(".vcs/HEAD")?.to_string();
-    let mut content = String::from(".vcs/");
-    content.push_str(&current_branch);
-    let main_branch_content = fs::read_to_string(content)?;
-    let branches_path = Path::new(".vcs").join("refs").join("heads").join(msg);
-    let mut new_file = fs::File::create(branches_path)?;
-    new_file.write_all(&main_branch_content.into_bytes())?;
+use colored::*;
+
+fn branch_ref(name: &str) -> String {
+    format!("refs/heads/{}", name)
+}
+
+// The commit a branch points at, an error when there is no such branch.
+fn branch_commit(name: &str) -> Result<String> {
+    refs::read_ref(&branch_ref(name))?.ok_or_else(|| anyhow!("Branch '{}' does not exist.", name))
+}
+
+fn check_room(name: &str) -> Result<()> {
+    match refs::clashing_ref(&branch_ref(name))? {
+        Some(existing) => Err(anyhow!(
+            "Cannot create branch '{}', it clashes with the existing ref '{}'.",
+            name,
+            existing
+        )),
+        None => Ok(()),
+    }
+}
+
+// Creates a branch at the start point, HEAD when none is given.
+pub fn branch_command(msg: &str, start_point: Option<&str>) -> Result<()> {
+    refs::check_ref_name(msg)?;
+    if refs::read_ref(&branch_ref(msg))?.is_some() {
+        return Err(anyhow!("A branch named '{}' already exists.", msg));
+    }
+    check_room(msg)?;
+    let commit = match start_point {
+        Some(start_point) => revision::resolve_commit(start_point)?,
+        None => file_altering::get_head_commit()?.ok_or_else(|| {
+            anyhow!(
+                "Cannot create branch '{}', the current branch has no commits yet.",
+                msg
+            )
+        })?,
+    };
+    let reason = format!("branch: Created from {}", start_point.unwrap_or("HEAD"));
+    refs::update_ref(&branch_ref(msg), &commit, &reason)?;
+    Ok(())
+}
+
+// Every branch, the current one is starred, a detached HEAD is listed first.
+pub fn list_branches() -> Result<()> {
+    let current = file_altering::get_head_branch()?;
+    if current.is_none() {
+        if let Some(commit) = file_altering::get_head_commit()? {
+            println!(
+                "* {}",
+                format!("(HEAD detached at {})", &commit[..7]).green()
+            );
+        }
+    }
+    for (name, _) in refs::list_refs()? {
+        let Some(branch) = name.strip_prefix("refs/heads/") else {
+            continue;
+        };
+        if current.as_deref() == Some(branch) {
+            println!("* {}", branch.green());
+        } else {
+            println!("  {}", branch);
+        }
+    }
+    Ok(())
+}
+
+/*
+ * Deletes a branch. Without force the branch has to be merged into HEAD, so
+ * that no commit is lost with it.
+ * */
+pub fn delete_branch(name: &str, force: bool) -> Result<()> {
+    let commit = branch_commit(name)?;
+    if file_altering::get_head_branch()?.as_deref() == Some(name) {
+        return Err(anyhow!(
+            "Cannot delete branch '{}', it is checked out.",
+            name
+        ));
+    }
+    if !force {
+        let merged = match file_altering::get_head_commit()? {
+            Some(head) => merge_base::is_ancestor(&commit, &head)?,
+            None => false,
+        };
+        if !merged {
+            return Err(anyhow!(
+                "The branch '{}' is not fully merged, use -D to delete it anyway.",
+                name
+            ));
+        }
+    }
+    refs::delete_ref(&branch_ref(name))?;
+    reflog::delete(&branch_ref(name))?;
+    println!("Deleted branch {} (was {}).", name, &commit[..7]);
+    Ok(())
+}
+
+/*
+ * Renames a branch, the current one by default, HEAD follows when it is on it.
+ * The old ref is only deleted once the new one is written with its log.
+ * */
+pub fn rename_branch(old: Option<&str>, new: &str) -> Result<()> {
+    refs::check_ref_name(new)?;
+    let current = file_altering::get_head_branch()?;
+    let old = match old.or(current.as_deref()) {
+        Some(old) => old,
+        None => return Err(anyhow!("HEAD is detached, there is no branch to rename.")),
+    };
+    let commit = branch_commit(old)?;
+    if old == new {
+        return Ok(());
+    }
+    if refs::read_ref(&branch_ref(new))?.is_some() {
+        return Err(anyhow!("A branch named '{}' already exists.", new));
+    }
+    check_room(new)?;
+    refs::write_ref(&branch_ref(new), &commit)?;
+    if refs::read_ref(&branch_ref(new))?.as_deref() != Some(commit.as_str()) {
+        return Err(anyhow!("Failed to write the branch '{}'.", new));
+    }
+    reflog::rename(&branch_ref(old), &branch_ref(new))?;
+    let reason = format!("Branch: renamed {} to {}", branch_ref(old), branch_ref(new));
+    reflog::append(&branch_ref(new), Some(&commit), &commit, &reason)?;
+    refs::delete_ref(&branch_ref(old))?;
+    if current.as_deref() == Some(old) {
+        checkout::move_head_pointer(new)?;
+    }
     Ok(())
 }
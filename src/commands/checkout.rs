@@ -1,13 +1,29 @@
-use crate::commands::dec_object::dec_obj;
-use crate::objects::commit;
-use crate::others::file_altering;
-use crate::others::index;
+use crate::commands::{branch, merge, merge_base};
+use crate::objects::store::{self, ObjectKind};
+use crate::objects::tree::{self, Tree};
+use crate::others::{diff3, file_altering, index, reflog, refs, revision};
 use anyhow::anyhow;
 use anyhow::Result;
-use std::collections::HashMap;
+use colored::*;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/*
+ * What happens to local changes in the files a checkout has to touch:
+ *     Safe   the checkout is refused and the files are listed
+ *     Force  they are thrown away
+ *     Merge  they are merged into the version of the target, conflicts are
+ *            written with markers
+ * Local changes to files that are the same in both trees are always kept.
+ * */
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CheckoutMode {
+    Safe,
+    Force,
+    Merge,
+}
 
 pub fn move_head_pointer(branch_name: &str) -> Result<()> {
     let head_path = Path::new(".vcs").join("HEAD");
@@ -19,54 +35,358 @@ pub fn move_head_pointer(branch_name: &str) -> Result<()> {
 }
 
 pub fn get_files_from_tree(tree_hash: &str) -> Result<HashMap<String, String>> {
-    let tree_content = dec_obj(tree_hash)?;
     let mut file_map = HashMap::new();
-    for line in tree_content.lines() {
-        let parsed_object = index::ObjectInfo::from_pretty_print(line)?;
-        if parsed_object.obj_type == "blob" {
-            let path = parsed_object.path.to_str().unwrap();
-            file_map.insert(path.to_string(), parsed_object.hash.clone());
-        }
+    for parsed_object in Tree::flatten(tree_hash)?.obj {
+        let path = parsed_object.path.to_str().unwrap();
+        file_map.insert(path.to_string(), parsed_object.hash.clone());
     }
     Ok(file_map)
 }
 
-pub fn checkout(branch: &str) -> Result<()> {
-    let head_path = Path::new(".vcs").join("HEAD");
+fn file_modes(tree_hash: &str) -> Result<HashMap<String, u32>> {
+    Ok(Tree::flatten(tree_hash)?
+        .obj
+        .into_iter()
+        .map(|obj| (obj.path.to_str().unwrap().to_string(), obj.permissions))
+        .collect())
+}
+
+fn working_hash(path: &str) -> Result<Option<String>> {
+    if !Path::new(path).is_file() {
+        return Ok(None);
+    }
+    Ok(Some(store::hash_object(ObjectKind::Blob, &fs::read(path)?)))
+}
+
+/*
+ * Paths among `paths` whose content in the index or the working tree differs
+ * from the `from` tree, split into tracked files with local changes and
+ * untracked files. A version that is already the target one loses nothing.
+ * */
+fn local_changes(
+    paths: &[&String],
+    from: &HashMap<String, String>,
+    to: &HashMap<String, String>,
+    index: &HashMap<String, String>,
+) -> Result<(Vec<String>, Vec<String>)> {
+    let mut modified = Vec::new();
+    let mut untracked = Vec::new();
+    for &path in paths {
+        let (committed, target) = (from.get(path), to.get(path));
+        let staged = index.get(path);
+        let working = working_hash(path)?;
+        let index_lost = staged != committed && staged != target;
+        let working_lost =
+            working.is_some() && working.as_ref() != committed && working.as_ref() != target;
+        if !index_lost && !working_lost {
+            continue;
+        }
+        if committed.is_none() && staged.is_none() {
+            untracked.push(path.clone());
+        } else {
+            modified.push(path.clone());
+        }
+    }
+    Ok((modified, untracked))
+}
+
+// Refuses when any of `paths` has changes that are not in the `from` tree.
+pub fn ensure_unchanged(from_tree: &str, paths: &[String]) -> Result<()> {
+    let from = get_files_from_tree(from_tree)?;
+    let staged: HashMap<String, String> = index::Index::load()?
+        .obj
+        .into_iter()
+        .filter(|obj| obj.stage == 0)
+        .map(|obj| (obj.path.to_str().unwrap().to_string(), obj.hash))
+        .collect();
+    let paths: Vec<&String> = paths.iter().collect();
+    let (modified, untracked) = local_changes(&paths, &from, &HashMap::new(), &staged)?;
+    if modified.is_empty() && untracked.is_empty() {
+        return Ok(());
+    }
+    Err(refuse(&modified, &untracked))
+}
+
+fn refuse(modified: &[String], untracked: &[String]) -> anyhow::Error {
+    let mut output = String::new();
+    if !modified.is_empty() {
+        output.push_str("Your local changes to these files would be overwritten by checkout:");
+        for path in modified {
+            output.push_str(&format!("\n\t{}", path));
+        }
+        output.push('\n');
+    }
+    if !untracked.is_empty() {
+        output.push_str("These untracked files would be overwritten by checkout:");
+        for path in untracked {
+            output.push_str(&format!("\n\t{}", path));
+        }
+        output.push('\n');
+    }
+    output
+        .push_str("Commit or remove them, or use --force to discard them or --merge to keep them.");
+    anyhow!(output)
+}
+
+/*
+ * Carries the local version of a file over to the target: the committed version
+ * is the base, the target and the working file are the two sides. Returns false
+ * when the file was left with conflicts.
+ * */
+fn merge_local_changes(
+    path: &str,
+    from: Option<&String>,
+    to: Option<&String>,
+    file_mode: u32,
+    target_label: &str,
+) -> Result<bool> {
+    let Ok(local) = fs::read(path) else {
+        // deleted locally, the target version simply comes back
+        if let Some(hash) = to {
+            let content = store::read_object_of(hash, ObjectKind::Blob)?;
+            file_altering::write_working_file(Path::new(path), &content, file_mode)?;
+        }
+        return Ok(true);
+    };
+    let Some(to) = to else {
+        println!("Keeping local changes to {}, it is untracked now", path);
+        return Ok(true);
+    };
+    let base = match from {
+        Some(hash) => store::read_object_of(hash, ObjectKind::Blob)?,
+        None => Vec::new(),
+    };
+    let target = store::read_object_of(to, ObjectKind::Blob)?;
+    let (Ok(base), Ok(target_text), Ok(local_text)) = (
+        String::from_utf8(base),
+        String::from_utf8(target),
+        String::from_utf8(local),
+    ) else {
+        // binary files cannot be merged, the local file stays as it is
+        return Ok(false);
+    };
+    let merged = diff3::merge(
+        &base,
+        &target_text,
+        &local_text,
+        target_label,
+        "local",
+        false,
+    );
+    file_altering::write_working_file(Path::new(path), merged.content.as_bytes(), file_mode)?;
+    Ok(merged.conflicts == 0)
+}
+
+/*
+ * Moves the working tree and the index from the `from` tree (None before the
+ * first commit) to the `to` tree. Only files that differ between the two trees
+ * are written or removed, anything else in the working tree and the index is
+ * left alone, unless the checkout is forced.
+ * */
+pub fn switch_trees(
+    from_tree: Option<&str>,
+    to_tree: &str,
+    mode: CheckoutMode,
+    target_label: &str,
+) -> Result<()> {
+    let from = match from_tree {
+        Some(tree) => get_files_from_tree(tree)?,
+        None => HashMap::new(),
+    };
+    let from_modes = match from_tree {
+        Some(tree) => file_modes(tree)?,
+        None => HashMap::new(),
+    };
+    let to = get_files_from_tree(to_tree)?;
+    let to_modes = file_modes(to_tree)?;
+    let target_index = file_altering::build_index_from_tree(to_tree)?;
+    let mut current_index = index::Index::load()?;
+    let staged: HashMap<String, String> = current_index
+        .obj
+        .iter()
+        .filter(|obj| obj.stage == 0)
+        .map(|obj| (obj.path.to_str().unwrap().to_string(), obj.hash.clone()))
+        .collect();
+
+    let changed: Vec<&String> = from
+        .keys()
+        .chain(to.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|path| {
+            mode == CheckoutMode::Force
+                || from.get(*path) != to.get(*path)
+                || from_modes.get(*path) != to_modes.get(*path)
+        })
+        .collect();
+    let (modified, untracked) = local_changes(&changed, &from, &to, &staged)?;
+    if mode == CheckoutMode::Safe && !(modified.is_empty() && untracked.is_empty()) {
+        return Err(refuse(&modified, &untracked));
+    }
+
+    // removals first, a file can be replaced by a directory of the same name
+    let mut conflicts = Vec::new();
+    let mut writes = Vec::new();
+    for &path in &changed {
+        let dirty = modified.contains(path) || untracked.contains(path);
+        if mode == CheckoutMode::Merge && dirty {
+            writes.push((path, true));
+        } else if to.contains_key(path) {
+            writes.push((path, false));
+        } else {
+            file_altering::remove_working_file(Path::new(path))?;
+        }
+    }
+    for (path, merge_local) in writes {
+        let file_mode = to_modes.get(path).copied().unwrap_or(tree::MODE_FILE);
+        if merge_local {
+            if !merge_local_changes(path, from.get(path), to.get(path), file_mode, target_label)? {
+                conflicts.push(path.clone());
+            }
+            continue;
+        }
+        let content = store::read_object_of(&to[path], ObjectKind::Blob)?;
+        file_altering::write_working_file(Path::new(path), &content, file_mode)?;
+    }
+
+    // the index takes the target entries of the changed paths and keeps the rest
+    if mode == CheckoutMode::Force {
+        current_index = target_index;
+    } else {
+        let changed_paths: Vec<PathBuf> = changed.iter().map(PathBuf::from).collect();
+        current_index
+            .obj
+            .retain(|obj| !changed_paths.contains(&obj.path));
+        for obj in target_index.obj {
+            if changed_paths.contains(&obj.path) {
+                current_index.add_object(obj);
+            }
+        }
+    }
+    current_index.save_index_file_truncate()?;
+
+    for path in conflicts {
+        println!("{}: Merge conflict in {}", "CONFLICT".red().bold(), path);
+    }
+    Ok(())
+}
+
+pub fn checkout(target: &str) -> Result<()> {
+    checkout_with_mode(target, CheckoutMode::Safe)
+}
 
-    let mut current_branch = String::from(".vcs/");
-    let content = fs::read_to_string(&head_path)?.trim().to_string();
-    current_branch.push_str(&content);
-    let current_commit_hash = fs::read_to_string(&current_branch)?.trim().to_string();
-    let current_pretty_commit = dec_obj(&current_commit_hash)?;
-    let current_commit_content_obj =
-        commit::CommitContent::from_pretty_print(&current_pretty_commit)?;
-    let current_tree = current_commit_content_obj.tree;
-
-    let target_branch_path = Path::new(".vcs").join("refs").join("heads").join(branch);
-    if !target_branch_path.exists() {
-        return Err(anyhow!("Branch '{}' does not exist.", branch));
-    }
-    let target_commit_hash = fs::read_to_string(&target_branch_path)?.trim().to_string();
-    let pretty_commit = dec_obj(&target_commit_hash)?;
-    let commit_content_obj = commit::CommitContent::from_pretty_print(&pretty_commit)?;
-    let target_tree = commit_content_obj.tree;
-    let current_files = get_files_from_tree(&current_tree)?;
-    let target_files = get_files_from_tree(&target_tree)?;
-    let target_index = file_altering::build_index_from_tree(&target_tree)?;
-    for file in current_files.keys() {
-        if !target_files.contains_key(file) {
-            fs::remove_file(file)?;
+pub fn checkout_with_mode(target: &str, mode: CheckoutMode) -> Result<()> {
+    if merge::merge_head()?.is_some() {
+        if mode != CheckoutMode::Force {
+            return Err(anyhow!(
+                "A merge is in progress, finish it first or use --force to drop it"
+            ));
         }
+        merge::clear_merge_state()?;
     }
+    // a branch name wins over a commit, anything else detaches HEAD
+    let target_branch_path = Path::new(".vcs").join("refs").join("heads").join(target);
+    let branch = target_branch_path.is_file();
+    let target_commit_hash = if branch {
+        fs::read_to_string(&target_branch_path)?.trim().to_string()
+    } else {
+        revision::resolve_commit(target)
+            .map_err(|_| anyhow!("'{}' is neither a branch nor a commit.", target))?
+    };
+    let target_tree = file_altering::get_tree_from_commit(&target_commit_hash)?;
+    let current_commit = file_altering::get_head_commit()?;
+    let current_tree = match &current_commit {
+        Some(commit) => Some(file_altering::get_tree_from_commit(commit)?),
+        None => None,
+    };
 
-    for (file, hash) in &target_files {
-        let content = dec_obj(hash)?;
-        fs::write(file, content)?;
+    // with the same tree, the working tree and the staged changes stay as they are
+    if mode == CheckoutMode::Force || current_tree.as_deref() != Some(target_tree.as_str()) {
+        switch_trees(current_tree.as_deref(), &target_tree, mode, target)?;
+    }
+    let previous_branch = file_altering::get_head_branch()?;
+    let was_detached = previous_branch.is_none();
+    if branch {
+        move_head_pointer(target)?;
+        println!("Switched to branch '{}'.", target);
+    } else {
+        detach_head(&target_commit_hash)?;
+        println!(
+            "HEAD is now at {} (detached), new commits will not be on any branch.",
+            &target_commit_hash[..7]
+        );
     }
+    if let Some(previous) = &current_commit {
+        let from = previous_branch.unwrap_or_else(|| previous.clone());
+        let reason = format!("checkout: moving from {} to {}", from, target);
+        reflog::append("HEAD", Some(previous), &target_commit_hash, &reason)?;
+    }
+    if let (true, Some(previous)) = (was_detached, current_commit) {
+        warn_left_behind(&previous)?;
+    }
+    Ok(())
+}
 
-    move_head_pointer(branch)?;
-    target_index.save_index_file_truncate()?;
-    println!("Switched to branch '{}'.", branch);
+pub fn detach_head(commit_hash: &str) -> Result<()> {
+    fs::write(Path::new(".vcs").join("HEAD"), commit_hash)?;
     Ok(())
 }
+
+// Commits made on a detached HEAD are lost once nothing points at them any more.
+fn warn_left_behind(previous: &str) -> Result<()> {
+    for (_, hash) in refs::list_refs()? {
+        if merge_base::is_ancestor(previous, &hash)? {
+            return Ok(());
+        }
+    }
+    if file_altering::get_head_commit()?.as_deref() == Some(previous) {
+        return Ok(());
+    }
+    println!(
+        "{}: commit {} is not on any branch and is left behind.",
+        "Warning".yellow().bold(),
+        &previous[..7]
+    );
+    Ok(())
+}
+
+/*
+ * Creates a branch at the start point, HEAD by default, and switches to it. The
+ * branch is removed again when the switch is refused.
+ * */
+pub fn checkout_new_branch(
+    name: &str,
+    start_point: Option<&str>,
+    mode: CheckoutMode,
+) -> Result<()> {
+    if start_point.is_none() && file_altering::get_head_commit()?.is_none() {
+        // nothing committed yet, the new branch is born with the first commit
+        refs::check_ref_name(name)?;
+        move_head_pointer(name)?;
+        println!("Switched to a new branch '{}'.", name);
+        return Ok(());
+    }
+    branch::branch_command(name, start_point)?;
+    if let Err(err) = checkout_with_mode(name, mode) {
+        refs::delete_ref(&format!("refs/heads/{}", name))?;
+        return Err(err);
+    }
+    Ok(())
+}
+
+// Like checkout, limited to branches: HEAD is never detached.
+pub fn switch_command(branch: Option<&str>, create: Option<&str>) -> Result<()> {
+    match (create, branch) {
+        (Some(name), start_point) => checkout_new_branch(name, start_point, CheckoutMode::Safe),
+        (None, Some(branch)) => {
+            if refs::read_ref(&format!("refs/heads/{}", branch))?.is_none() {
+                return Err(anyhow!(
+                    "'{}' is not a branch, use `rvcs checkout` to detach HEAD at a commit.",
+                    branch
+                ));
+            }
+            checkout(branch)
+        }
+        (None, None) => Err(anyhow!("A branch to switch to is required.")),
+    }
+}
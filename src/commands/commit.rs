@@ -1,50 +1,36 @@
-use crate::commands::diff;
-use crate::objects::commit::Commit;
+use crate::commands::{diff, merge};
+use crate::objects::commit::{Commit, Signature, SignatureOverrides};
 use crate::objects::tree::Tree;
-use crate::others::{file_altering, index};
+use crate::others::{file_altering, index, revision};
 use anyhow::anyhow;
 use anyhow::Result;
 use std::collections::HashMap;
-use std::fs;
-use std::io::Write;
-use std::path::Path;
-pub fn commit_tree_command(tree_hash: &str, message: &str) -> Result<()> {
-    let head_path = Path::new(".vcs").join("HEAD");
+pub fn commit_tree_command(
+    tree_hash: &str,
+    message: &str,
+    overrides: &SignatureOverrides,
+) -> Result<()> {
+    let tree_hash = revision::resolve_tree(tree_hash)?;
     // Determine the parent commit, if it exists
-    let parent_commit = if head_path.exists() {
-        let branch_ref = fs::read_to_string(&head_path)?.trim().to_string();
-        let branch_path = Path::new(".vcs").join(branch_ref);
-        if branch_path.exists() {
-            let temp = fs::read_to_string(&branch_path)?.trim().to_string();
-            if temp.is_empty() {
-                None
-            } else {
-                Some(fs::read_to_string(&branch_path)?.trim().to_string())
-            }
-        } else {
-            None
-        }
-    } else {
-        None
-    };
+    let parent_commit = file_altering::get_head_commit()?;
     let parents: Vec<String> = parent_commit.into_iter().collect();
-    let commit = Commit::new(tree_hash.to_string(), parents.clone(), message.to_string());
+    let (author, committer) = Signature::for_commit(overrides)?;
+    let commit = Commit::new(
+        tree_hash,
+        parents.clone(),
+        author,
+        committer,
+        message.to_string(),
+    );
     commit.create_commit()?;
 
-    // Update the current branch reference to point to the new commit
-    if head_path.exists() {
-        let branch_ref = fs::read_to_string(&head_path)?.trim().to_string();
-
-        let branch_path = Path::new(".vcs").join(branch_ref);
-
-        let mut branch_file =
-            fs::File::create(branch_path).map_err(|e| anyhow!("Failed to update branch: {}", e))?;
-        branch_file.write_all(commit.id.as_bytes())?;
+    // Update the current branch reference, or HEAD when detached, to point to the new commit
+    let reason = if parents.is_empty() {
+        format!("commit (initial): {}", message)
     } else {
-        return Err(anyhow!(
-            "HEAD file not found. Is the repository initialized?"
-        ));
-    }
+        format!("commit: {}", message)
+    };
+    file_altering::update_head(&commit.id, &reason)?;
     /*
      * For better print i will diff between two commits
      *
@@ -81,7 +67,8 @@ pub fn get_sections(diff_output: &str) -> Result<Vec<Vec<String>>> {
 pub fn detailed_print(commit_hash: &str, parent_commit: &str) -> Result<String> {
     let mut output = String::new();
 
-    let diff_output = diff::diff_between_commits(parent_commit, commit_hash)?;
+    let diff_output =
+        diff::diff_between_commits(parent_commit, commit_hash, &diff::DiffOptions::default())?;
 
     let sections = get_sections(&diff_output)?;
     let mut add_ct: u32 = 0;
@@ -91,6 +78,7 @@ pub fn detailed_print(commit_hash: &str, parent_commit: &str) -> Result<String>
     let mut file_name = String::new();
     for section in sections.iter() {
         for line in section.iter() {
+            let line = &diff::strip_ansi_codes(line);
             if line.starts_with("Added") {
                 file_name = line["Added file: ".len()..].to_string();
                 map.insert(file_name.clone(), (0, 0));
@@ -139,15 +127,18 @@ pub fn detailed_print(commit_hash: &str, parent_commit: &str) -> Result<String>
     Ok(output)
 }
 
-pub fn commit_command(msg: &str) -> Result<()> {
+pub fn commit_command(msg: &str, overrides: &SignatureOverrides) -> Result<()> {
+    // committing while a merge is in progress concludes the merge
+    if merge::merge_head()?.is_some() {
+        return merge::merge_continue(Some(msg), overrides);
+    }
     let current_tree_hash = match file_altering::get_current_tree() {
         Ok(curent_tree_hash) => {
-            let (mut current_files, deleted_files) =
-                file_altering::get_files_from_tree(&curent_tree_hash)?;
+            let (_, deleted_files) = file_altering::get_files_from_tree(&curent_tree_hash)?;
 
-            let size = deleted_files.len();
-            let true_index = index::Index::clean_index(&mut current_files, deleted_files)?;
-            if size != 0 {
+            if !deleted_files.is_empty() {
+                let mut current_index = index::Index::load()?;
+                let true_index = index::Index::clean_index(&mut current_index, deleted_files)?;
                 true_index.save_index_file_truncate()?;
             }
             curent_tree_hash
@@ -158,26 +149,30 @@ pub fn commit_command(msg: &str) -> Result<()> {
         }
     };
     let tree = Tree::new()?;
-    tree.create_tree()?;
     if !current_tree_hash.is_empty() && current_tree_hash == tree.id {
         return Err(anyhow!("No changes detected. Commit aborted."));
     }
-    commit_tree_command(&tree.id, msg)?;
+    tree.create_tree()?;
+    commit_tree_command(&tree.id, msg, overrides)?;
     Ok(())
 }
 
-use crate::objects::tree;
-
-pub fn create_merge_commit(commit1: &str, commit2: &str) -> Result<()> {
-    let tree1 = file_altering::get_tree_from_commit(commit1)?;
-    let tree2 = file_altering::get_tree_from_commit(commit2)?;
-    let index1 = tree::Tree::merge_tree(&tree1, &tree2)?;
-    let index2 = tree::Tree::merge_tree(&tree1, &tree2)?;
-    let merged_tree = tree::Tree::new_tree_from_index(index1)?;
-    merged_tree.create_tree_from_index(index2)?;
-
+pub fn create_merge_commit(
+    commit1: &str,
+    commit2: &str,
+    tree_hash: &str,
+    message: &str,
+    overrides: &SignatureOverrides,
+) -> Result<()> {
     let parents: Vec<String> = vec![commit1.to_string(), commit2.to_string()];
-    let merged_commit = Commit::new(merged_tree.id, parents, "MERGE COMMIT".to_string());
+    let (author, committer) = Signature::for_commit(overrides)?;
+    let merged_commit = Commit::new(
+        tree_hash.to_string(),
+        parents,
+        author,
+        committer,
+        message.to_string(),
+    );
     merged_commit.create_commit()?;
 
     println!(
@@ -185,13 +180,7 @@ pub fn create_merge_commit(commit1: &str, commit2: &str) -> Result<()> {
         merged_commit.id
     );
 
-    let head_path = Path::new(".vcs").join("HEAD");
-    let branch_ref = fs::read_to_string(&head_path)?.trim().to_string();
-    let branch_path = Path::new(".vcs").join(branch_ref);
-
-    let mut branch_file =
-        fs::File::create(branch_path).map_err(|e| anyhow!("Failed to update branch: {}", e))?;
-    branch_file.write_all(merged_commit.id.as_bytes())?;
+    file_altering::update_head(&merged_commit.id, &format!("commit (merge): {}", message))?;
 
     Ok(())
 }
@@ -0,0 +1,22 @@
+use crate::others::config;
+use anyhow::{anyhow, Result};
+
+pub fn config_command(key: Option<&String>, value: Option<&String>, list: bool) -> Result<()> {
+    if list {
+        for (key, value) in config::list()? {
+            println!("{}={}", key, value);
+        }
+        return Ok(());
+    }
+    let key = key.ok_or_else(|| anyhow!("A config key is required"))?;
+    match value {
+        Some(value) => config::set(key, value),
+        None => match config::get(key)? {
+            Some(value) => {
+                println!("{}", value);
+                Ok(())
+            }
+            None => Err(anyhow!("Config key '{}' is not set", key)),
+        },
+    }
+}
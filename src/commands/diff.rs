@@ -1,46 +1,120 @@
-use crate::commands::dec_object;
+use crate::objects::store::{self, ObjectKind};
+use crate::objects::tree::Tree;
 use crate::others::file_altering;
 use crate::others::index;
-use anyhow::Result;
+use crate::others::line_diff::{self, DiffAlgorithm, Edit};
+use crate::others::revision;
+use anyhow::{anyhow, Result};
 use colored::*;
+use regex::Regex;
 use std::collections::{HashMap, HashSet};
 
-pub fn diff_between_files(file1: &str, file2: &str) -> Result<String> {
-    let content1 = file_altering::get_file_content(file1)?;
-    let content2 = file_altering::get_file_content(file2)?;
+pub const DEFAULT_CONTEXT: usize = 3;
+
+pub struct DiffOptions {
+    pub algorithm: DiffAlgorithm,
+    pub context: usize, // unchanged lines shown around every change
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            algorithm: DiffAlgorithm::Myers,
+            context: DEFAULT_CONTEXT,
+        }
+    }
+}
+
+pub fn strip_ansi_codes(input: &str) -> String {
+    // Regex to match ANSI escape codes
+    let ansi_regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    ansi_regex.replace_all(input, "").to_string()
+}
+
+pub fn diff_between_files(file1: &str, file2: &str, options: &DiffOptions) -> Result<String> {
+    let content1 = file_altering::get_file_bytes(file1)?;
+    let content2 = file_altering::get_file_bytes(file2)?;
     let header = format!("{} {} -> {}\n", "@@".blue().bold(), file1, file2);
     println!("{}", header);
-    Ok(diff_between_content(&content1, &content2))
+    Ok(diff_between_bytes(&content1, &content2, options))
+}
+
+// A NUL byte in the first 8000 bytes marks the file as binary, like git does.
+// Content that is not valid UTF-8 anywhere is binary too, the line diff needs text.
+pub fn is_binary(content: &[u8]) -> bool {
+    let probe = &content[..content.len().min(8000)];
+    probe.contains(&0) || std::str::from_utf8(content).is_err()
+}
+
+pub fn diff_between_bytes(content1: &[u8], content2: &[u8], options: &DiffOptions) -> String {
+    if is_binary(content1) || is_binary(content2) {
+        if content1 == content2 {
+            return String::new();
+        }
+        return String::from("Binary files differ\n");
+    }
+    // both sides were checked above, the conversion cannot fail
+    let text1 = String::from_utf8_lossy(content1);
+    let text2 = String::from_utf8_lossy(content2);
+    diff_between_content(&text1, &text2, options)
+}
+
+pub fn diff_between_blobs(
+    hash1: Option<&str>,
+    hash2: Option<&str>,
+    options: &DiffOptions,
+) -> Result<String> {
+    let content1 = match hash1 {
+        Some(hash) => store::read_object_of(hash, ObjectKind::Blob)?,
+        None => Vec::new(),
+    };
+    let content2 = match hash2 {
+        Some(hash) => store::read_object_of(hash, ObjectKind::Blob)?,
+        None => Vec::new(),
+    };
+    Ok(diff_between_bytes(&content1, &content2, options))
 }
 
-pub fn diff_between_content(content1: &str, content2: &str) -> String {
-    let lines1: Vec<&str> = content1.lines().collect();
-    let lines2: Vec<&str> = content2.lines().collect();
+/*
+ * Unified diff of two texts, one "@@ -a,b +c,d @@" hunk per group of changes.
+ * Lines keep their newline while they are compared, so a missing newline at the
+ * end of the file shows up as a change, like diff -u does.
+ * */
+pub fn diff_between_content(content1: &str, content2: &str, options: &DiffOptions) -> String {
+    let lines1: Vec<&str> = content1.split_inclusive('\n').collect();
+    let lines2: Vec<&str> = content2.split_inclusive('\n').collect();
+    let edits = line_diff::diff(&lines1, &lines2, options.algorithm);
     let mut diff_result = String::new();
 
-    let mut i = 0;
-    let mut j = 0;
-
-    while i < lines1.len() || j < lines2.len() {
-        if i < lines1.len() && j < lines2.len() && lines1[i] == lines2[j] {
-            diff_result.push_str(&format!("  {}\n", lines1[i]));
-            i += 1;
-            j += 1;
-        } else if i < lines1.len() && (j >= lines2.len() || lines1[i] != lines2[j]) {
-            // Line removed
-            diff_result.push_str(&format!("{} {}\n", "-", lines1[i].red()));
-            i += 1;
-        } else if j < lines2.len() && (i >= lines1.len() || lines1[i] != lines2[j]) {
-            // Line added
-            diff_result.push_str(&format!("{} {}\n", "+", lines2[j].green()));
-            j += 1;
+    for hunk in line_diff::hunks(&edits, options.context) {
+        diff_result.push_str(&format!("{}\n", hunk.header().cyan()));
+        for (edit, line) in hunk.lines {
+            let (sign, text) = match edit {
+                Edit::Equal => (' ', lines1[line]),
+                Edit::Delete => ('-', lines1[line]),
+                Edit::Insert => ('+', lines2[line]),
+            };
+            let line = format!("{}{}", sign, text.trim_end_matches('\n'));
+            let line = match edit {
+                Edit::Equal => line.normal(),
+                Edit::Delete => line.red(),
+                Edit::Insert => line.green(),
+            };
+            diff_result.push_str(&format!("{}\n", line));
+            if !text.ends_with('\n') {
+                diff_result.push_str("\\ No newline at end of file\n");
+            }
         }
     }
 
     diff_result
 }
 
-pub fn diff_between_obj(obj1: index::Index, obj2: index::Index) -> Result<String> {
+pub fn diff_between_obj(
+    obj1: index::Index,
+    obj2: index::Index,
+    options: &DiffOptions,
+) -> Result<String> {
     // Create maps for efficient lookup
     let mut content = String::new();
     let obj1_map: HashMap<String, &index::ObjectInfo> = obj1
@@ -73,9 +147,7 @@ pub fn diff_between_obj(obj1: index::Index, obj2: index::Index) -> Result<String
                     //println!("Modified file: {}", file.blue());
                     content.push_str(&temp);
                     content.push('\n');
-                    let content1 = dec_object::dec_obj(&file1.hash)?;
-                    let content2 = dec_object::dec_obj(&file2.hash)?;
-                    let diff = diff_between_content(&content1, &content2);
+                    let diff = diff_between_blobs(Some(&file1.hash), Some(&file2.hash), options)?;
                     //println!("{}", diff);
                     content.push_str(&diff);
                     content.push('\n');
@@ -94,8 +166,7 @@ pub fn diff_between_obj(obj1: index::Index, obj2: index::Index) -> Result<String
                 content.push_str(&temp);
                 content.push('\n');
                 //println!("Deleted file: {}", file1.path.to_str().unwrap().red());
-                let content1 = dec_object::dec_obj(&file1.hash)?;
-                let diff = diff_between_content(&content1, "");
+                let diff = diff_between_blobs(Some(&file1.hash), None, options)?;
                 //println!("{}", diff);
 
                 content.push_str(&diff);
@@ -110,8 +181,7 @@ pub fn diff_between_obj(obj1: index::Index, obj2: index::Index) -> Result<String
                 content.push_str(&temp);
                 content.push('\n');
                 //println!("Added file: {}", file2.path.to_str().unwrap().green());
-                let content1 = dec_object::dec_obj(&file2.hash)?;
-                let diff = diff_between_content("", &content1);
+                let diff = diff_between_blobs(None, Some(&file2.hash), options)?;
                 //println!("{}", diff);
 
                 content.push_str(&diff);
@@ -126,47 +196,47 @@ pub fn diff_between_obj(obj1: index::Index, obj2: index::Index) -> Result<String
     Ok(content)
 }
 
-pub fn diff_between_commits(commit_hash1: &str, commit_hash2: &str) -> Result<String> {
+pub fn diff_between_commits(
+    commit_hash1: &str,
+    commit_hash2: &str,
+    options: &DiffOptions,
+) -> Result<String> {
     let tree_hash1 = file_altering::get_tree_from_commit(commit_hash1)?;
     let tree_hash2 = file_altering::get_tree_from_commit(commit_hash2)?;
 
-    let mut branch1 = index::Index::new();
-    let tree_content1 = dec_object::dec_obj(&tree_hash1)?;
-    for line in tree_content1.lines() {
-        let obj = index::ObjectInfo::from_pretty_print(line)?;
-        branch1.add_object(obj);
-    }
+    let branch1 = Tree::flatten(&tree_hash1)?;
+    let branch2 = Tree::flatten(&tree_hash2)?;
 
-    let mut branch2 = index::Index::new();
-    let tree_content2 = dec_object::dec_obj(&tree_hash2)?;
-    for line in tree_content2.lines() {
-        let obj2 = index::ObjectInfo::from_pretty_print(line)?;
-        branch2.add_object(obj2);
-    }
-
-    let temp = diff_between_obj(branch1, branch2)?;
+    let temp = diff_between_obj(branch1, branch2, options)?;
     Ok(temp)
 }
 
-pub fn diff_between_current_last_commit() -> Result<()> {
-    // get current branch commit
-    let current_branch_temp = file_altering::get_curent_branch()?;
-    let current_branch = current_branch_temp["refs/heads/".len()..].to_string();
-    let current_commit_hash = file_altering::get_commit_from_branch(&current_branch)?;
+pub fn diff_between_current_last_commit(options: &DiffOptions) -> Result<()> {
+    let current_commit_hash = revision::resolve_commit("HEAD")?;
     let previous_commit_hash = file_altering::get_commit_parent(&current_commit_hash)?;
-    if previous_commit_hash.len() == 1 {
-        let temp = diff_between_commits(&previous_commit_hash[0], &current_commit_hash)?;
-        println!("{}", temp);
-    } else {
-        let temp = diff_between_commits(&previous_commit_hash[1], &current_commit_hash)?;
-        println!("{}", temp);
+    match previous_commit_hash.as_slice() {
+        [] => Err(anyhow!("The current commit has no parent to diff against")),
+        [parent] => {
+            let temp = diff_between_commits(parent, &current_commit_hash, options)?;
+            println!("{}", temp);
+            Ok(())
+        }
+        [_, parent, ..] => {
+            let temp = diff_between_commits(parent, &current_commit_hash, options)?;
+            println!("{}", temp);
+            Ok(())
+        }
     }
-    Ok(())
 }
 
-pub fn diff_between_branches(branch1: &str, branch2: &str) -> Result<()> {
-    let commit_hash1 = file_altering::get_commit_from_branch(branch1)?;
-    let commit_hash2 = file_altering::get_commit_from_branch(branch2)?;
-    diff_between_commits(&commit_hash1, &commit_hash2)?;
+pub fn diff_between_revisions(rev1: &str, rev2: &str, options: &DiffOptions) -> Result<String> {
+    let commit_hash1 = revision::resolve_commit(rev1)?;
+    let commit_hash2 = revision::resolve_commit(rev2)?;
+    diff_between_commits(&commit_hash1, &commit_hash2, options)
+}
+
+pub fn diff_between_branches(branch1: &str, branch2: &str, options: &DiffOptions) -> Result<()> {
+    let temp = diff_between_revisions(branch1, branch2, options)?;
+    println!("{}", temp);
     Ok(())
 }
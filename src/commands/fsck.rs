@@ -0,0 +1,226 @@
+use crate::objects::commit::CommitContent;
+use crate::objects::pack;
+use crate::objects::store::{self, ObjectKind};
+use crate::objects::tag::TagContent;
+use crate::objects::tree::TreeEntry;
+use crate::others::hash_function::calculate_hash;
+use crate::others::{index, reflog, refs};
+use anyhow::{anyhow, Result};
+use colored::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+#[derive(Default)]
+pub struct FsckReport {
+    pub missing: Vec<String>,
+    pub corrupt: Vec<String>,
+    pub malformed: Vec<String>,
+    pub dangling: Vec<String>,
+    pub bad_refs: Vec<String>,
+}
+
+impl FsckReport {
+    // Dangling objects are reported but are not an error, every amend or
+    // aborted commit leaves some behind until gc removes them.
+    pub fn is_healthy(&self) -> bool {
+        self.missing.is_empty()
+            && self.corrupt.is_empty()
+            && self.malformed.is_empty()
+            && self.bad_refs.is_empty()
+    }
+}
+
+// Reads one object and checks that its content hashes to its name.
+fn check_object(hash: &str, packed: bool) -> Result<(ObjectKind, Vec<u8>)> {
+    if packed {
+        let (kind, payload) =
+            pack::read_packed(hash)?.ok_or_else(|| anyhow!("object vanished from its pack"))?;
+        if store::hash_object(kind, &payload) != hash {
+            return Err(anyhow!("hash mismatch"));
+        }
+        return Ok((kind, payload));
+    }
+    let raw = store::read_loose_raw(hash)?;
+    if calculate_hash(&raw) != hash {
+        return Err(anyhow!("hash mismatch"));
+    }
+    store::decode(&raw)
+}
+
+// Parses the object and returns the objects it refers to with the type they must have.
+fn references(kind: ObjectKind, payload: &[u8]) -> Result<Vec<(String, ObjectKind)>> {
+    let mut refs = Vec::new();
+    match kind {
+        ObjectKind::Blob => {}
+        ObjectKind::Tree => {
+            let content = std::str::from_utf8(payload)?;
+            let mut names = HashSet::new();
+            for line in content.lines() {
+                let entry = TreeEntry::from_pretty_print(line)?;
+                store::object_path(&entry.hash)?;
+                if !names.insert(entry.name.clone()) {
+                    return Err(anyhow!("duplicate entry '{}'", entry.name));
+                }
+                if entry.name.contains('/') || entry.name == "." || entry.name == ".." {
+                    return Err(anyhow!("invalid entry name '{}'", entry.name));
+                }
+                refs.push((entry.hash, entry.kind));
+            }
+        }
+        ObjectKind::Commit => {
+            let commit = CommitContent::from_pretty_print(std::str::from_utf8(payload)?)?;
+            store::object_path(&commit.tree)?;
+            refs.push((commit.tree, ObjectKind::Tree));
+            for parent in commit.parents {
+                store::object_path(&parent)?;
+                refs.push((parent, ObjectKind::Commit));
+            }
+        }
+        ObjectKind::Tag => {
+            let tag = TagContent::from_pretty_print(std::str::from_utf8(payload)?)?;
+            store::object_path(&tag.object)?;
+            refs.push((tag.object, tag.kind));
+        }
+    }
+    Ok(refs)
+}
+
+pub fn fsck() -> Result<FsckReport> {
+    let mut report = FsckReport::default();
+
+    // every object we know about, loose objects win over packed copies
+    let mut all: BTreeMap<String, bool> = BTreeMap::new();
+    for idx_path in pack::list_packs()? {
+        let pack_path = idx_path.with_extension("pack");
+        if let Err(err) = pack::verify_checksum(&pack_path) {
+            report
+                .corrupt
+                .push(format!("pack {}: {}", pack_path.display(), err));
+            continue;
+        }
+        for hash in pack::PackIndex::open(&idx_path)?.hashes() {
+            all.insert(hash, true);
+        }
+    }
+    for hash in store::loose_objects()? {
+        all.insert(hash, false);
+    }
+
+    let mut kinds: HashMap<String, ObjectKind> = HashMap::new();
+    let mut referenced: Vec<(String, ObjectKind, String)> = Vec::new();
+    for (hash, packed) in &all {
+        let (kind, payload) = match check_object(hash, *packed) {
+            Ok(object) => object,
+            Err(err) => {
+                report.corrupt.push(format!("{}: {:#}", hash, err));
+                continue;
+            }
+        };
+        kinds.insert(hash.clone(), kind);
+        match references(kind, &payload) {
+            Ok(refs) => {
+                for (child, expected) in refs {
+                    referenced.push((child, expected, format!("{} {}", kind, hash)));
+                }
+            }
+            Err(err) => report
+                .malformed
+                .push(format!("{} {}: {:#}", kind, hash, err)),
+        }
+    }
+
+    let mut used: HashSet<String> = HashSet::new();
+    for (child, expected, parent) in &referenced {
+        used.insert(child.clone());
+        match kinds.get(child) {
+            Some(kind) if kind != expected => report.malformed.push(format!(
+                "{}: entry {} should be a {} but is a {}",
+                parent, child, expected, kind
+            )),
+            Some(_) => {}
+            None if all.contains_key(child) => {} // already reported as corrupt
+            None => report
+                .missing
+                .push(format!("{} {} (referenced by {})", expected, child, parent)),
+        }
+    }
+
+    // refs, HEAD and the index must point at existing objects
+    let mut roots = HashSet::new();
+    for (name, hash) in refs::list_refs()? {
+        roots.insert(hash.clone());
+        match kinds.get(&hash) {
+            Some(ObjectKind::Commit) => {}
+            Some(ObjectKind::Tag) if name.starts_with("refs/tags/") => {}
+            Some(kind) => report
+                .bad_refs
+                .push(format!("{}: points at a {} {}", name, kind, hash)),
+            None => report
+                .bad_refs
+                .push(format!("{}: points at nonexistent commit {}", name, hash)),
+        }
+    }
+    let head = fs::read_to_string(Path::new(".vcs").join("HEAD"))?
+        .trim()
+        .to_string();
+    if let Some(target) = head.strip_prefix("refs/") {
+        if !refs::refs_dir().join(target).exists() {
+            report
+                .bad_refs
+                .push(format!("HEAD: points at nonexistent ref {}", head));
+        }
+    } else {
+        roots.insert(head.clone());
+        if kinds.get(&head) != Some(&ObjectKind::Commit) {
+            report
+                .bad_refs
+                .push(format!("HEAD: points at nonexistent commit {}", head));
+        }
+    }
+    roots.extend(reflog::all_hashes()?);
+    for obj in index::Index::load()?.obj {
+        roots.insert(obj.hash.clone());
+        if !kinds.contains_key(&obj.hash) {
+            report.missing.push(format!(
+                "blob {} (staged in the index as {})",
+                obj.hash,
+                obj.path.display()
+            ));
+        }
+    }
+
+    for (hash, kind) in &kinds {
+        if !used.contains(hash) && !roots.contains(hash) {
+            report.dangling.push(format!("{} {}", kind, hash));
+        }
+    }
+
+    report.missing.sort();
+    report.missing.dedup();
+    report.dangling.sort();
+    Ok(report)
+}
+
+pub fn fsck_command() -> Result<bool> {
+    let report = fsck()?;
+    for line in &report.missing {
+        println!("{} {}", "missing".red().bold(), line);
+    }
+    for line in &report.corrupt {
+        println!("{} {}", "corrupt".red().bold(), line);
+    }
+    for line in &report.malformed {
+        println!("{} {}", "malformed".red().bold(), line);
+    }
+    for line in &report.bad_refs {
+        println!("{} {}", "bad ref".red().bold(), line);
+    }
+    for line in &report.dangling {
+        println!("{} {}", "dangling".yellow(), line);
+    }
+    if report.is_healthy() {
+        println!("No problems found.");
+    }
+    Ok(report.is_healthy())
+}
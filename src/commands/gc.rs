@@ -0,0 +1,198 @@
+use crate::objects::pack::{self, PackObject};
+use crate::objects::store;
+use crate::others::reachability;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+pub const DEFAULT_EXPIRE: &str = "2w";
+
+/*
+ * The grace period protects objects that were written a moment ago by a command
+ * that did not update any ref yet. Accepted values: "now", "never", or a number
+ * followed by s, m, h, d or w, a plain number means seconds.
+ * */
+pub fn parse_expire(value: &str) -> Result<Option<Duration>> {
+    match value {
+        "now" => return Ok(Some(Duration::ZERO)),
+        "never" => return Ok(None),
+        _ => {}
+    }
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("Invalid expire value: {}", value))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(anyhow!("Invalid expire unit: {}", unit)),
+    };
+    Ok(Some(Duration::from_secs(number * seconds)))
+}
+
+fn is_expired(path: &Path, grace: Option<Duration>) -> Result<bool> {
+    let Some(grace) = grace else {
+        return Ok(false);
+    };
+    let modified = fs::metadata(path)?.modified()?;
+    let age = SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or(Duration::ZERO);
+    Ok(age >= grace)
+}
+
+// Unreachable loose objects split into the ones past the grace period and the recent ones.
+fn unreachable_loose(
+    reachable: &HashSet<String>,
+    grace: Option<Duration>,
+) -> Result<(Vec<String>, Vec<String>)> {
+    let mut expired = Vec::new();
+    let mut recent = Vec::new();
+    for hash in store::loose_objects()? {
+        if reachable.contains(&hash) {
+            continue;
+        }
+        if is_expired(&store::object_path(&hash)?, grace)? {
+            expired.push(hash);
+        } else {
+            recent.push(hash);
+        }
+    }
+    Ok((expired, recent))
+}
+
+fn remove_expired(expired: &[String], dry_run: bool) -> Result<()> {
+    for hash in expired {
+        let (kind, _) = store::read_object(hash)?;
+        if dry_run {
+            println!("Would remove {} {}", kind, hash);
+        } else {
+            store::remove_loose_object(hash)?;
+            println!("Removed {} {}", kind, hash);
+        }
+    }
+    Ok(())
+}
+
+pub fn prune_command(dry_run: bool, expire: &str) -> Result<()> {
+    let grace = parse_expire(expire)?;
+    let reachable = reachability::reachable_objects(&reachability::roots()?)?;
+    let (expired, recent) = unreachable_loose(&reachable, grace)?;
+
+    remove_expired(&expired, dry_run)?;
+    if !recent.is_empty() {
+        println!(
+            "Kept {} unreachable objects younger than the grace period.",
+            recent.len()
+        );
+    }
+    if expired.is_empty() {
+        println!("Nothing to prune.");
+    }
+    Ok(())
+}
+
+/*
+ * gc prunes the expired unreachable loose objects and then moves every reachable
+ * object into a single new pack. Unreachable objects found in old packs are written
+ * back as loose objects when the pack is still inside the grace period, so a later
+ * gc can expire them, and dropped otherwise.
+ * */
+pub fn gc_command(dry_run: bool, expire: &str) -> Result<()> {
+    let grace = parse_expire(expire)?;
+    let reachable = reachability::reachable_objects(&reachability::roots()?)?;
+    let (expired, recent) = unreachable_loose(&reachable, grace)?;
+
+    let old_packs = pack::list_packs()?;
+    let mut unpack = Vec::new();
+    let mut dropped = 0;
+    for idx_path in &old_packs {
+        let pack_path = idx_path.with_extension("pack");
+        let pack_expired = is_expired(&pack_path, grace)?;
+        let pack_modified = fs::metadata(&pack_path)?.modified()?;
+        for hash in pack::PackIndex::open(idx_path)?.hashes() {
+            if reachable.contains(&hash) || store::object_path(&hash)?.exists() {
+                continue;
+            }
+            if pack_expired {
+                dropped += 1;
+                if dry_run {
+                    println!("Would drop packed object {}", hash);
+                }
+            } else {
+                unpack.push((hash, pack_modified));
+            }
+        }
+    }
+
+    let mut reachable: Vec<String> = reachable.into_iter().collect();
+    reachable.sort();
+
+    remove_expired(&expired, dry_run)?;
+    if dry_run {
+        println!(
+            "Would pack {} reachable objects, keep {} recent unreachable objects and drop {} packed ones.",
+            reachable.len(),
+            recent.len() + unpack.len(),
+            dropped
+        );
+        return Ok(());
+    }
+
+    let mut objects = Vec::new();
+    for hash in &reachable {
+        let (kind, payload) = store::read_object(hash)?;
+        objects.push(PackObject {
+            hash: hash.clone(),
+            kind,
+            payload,
+        });
+    }
+    // recent unreachable objects must be readable once the old packs are gone,
+    // they keep the age of their pack so the grace period is not restarted
+    for (hash, modified) in &unpack {
+        let (kind, payload) = store::read_object(hash)?;
+        store::write_loose_object(kind, &payload)?;
+        fs::File::options()
+            .write(true)
+            .open(store::object_path(hash)?)?
+            .set_modified(*modified)?;
+    }
+
+    let new_pack = if objects.is_empty() {
+        None
+    } else {
+        let pack_path = pack::write_pack(&objects, pack::DEFAULT_WINDOW, pack::DEFAULT_DEPTH)?;
+        pack::verify_objects(&pack_path, &objects)?;
+        Some(pack_path)
+    };
+
+    for idx_path in &old_packs {
+        if Some(idx_path.with_extension("pack")) == new_pack {
+            continue;
+        }
+        fs::remove_file(idx_path)?;
+        fs::remove_file(idx_path.with_extension("pack"))?;
+    }
+    for hash in &reachable {
+        if store::object_path(hash)?.exists() {
+            store::remove_loose_object(hash)?;
+        }
+    }
+
+    println!(
+        "Packed {} reachable objects, removed {} expired objects, kept {} recent unreachable objects.",
+        reachable.len(),
+        expired.len() + dropped,
+        recent.len() + unpack.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use crate::objects::commit::{Commit, CommitContent};
+use crate::others::{file_altering, refs, revision};
+use anyhow::{anyhow, Result};
+use colored::*;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LogOrder {
+    Date, // newest first, a commit is never shown before its children
+    Topo, // like Date, but the commits of one line of history stay together
+}
+
+pub struct LogOptions {
+    pub oneline: bool,
+    pub max_count: Option<usize>,
+    pub first_parent: bool,
+    pub order: LogOrder,
+    pub graph: bool,
+}
+
+fn parents_of(commit: &CommitContent, first_parent: bool) -> &[String] {
+    if first_parent && !commit.parents.is_empty() {
+        &commit.parents[..1]
+    } else {
+        &commit.parents
+    }
+}
+
+// Every commit reachable from the starting points, stopping at the hidden ones.
+pub fn collect_commits(
+    starts: &[String],
+    hidden: &HashSet<String>,
+    first_parent: bool,
+) -> Result<HashMap<String, CommitContent>> {
+    let mut commits = HashMap::new();
+    let mut to_visit = starts.to_vec();
+    while let Some(hash) = to_visit.pop() {
+        if commits.contains_key(&hash) || hidden.contains(&hash) {
+            continue;
+        }
+        let commit = Commit::read(&hash)?;
+        to_visit.extend(parents_of(&commit, first_parent).iter().cloned());
+        commits.insert(hash, commit);
+    }
+    Ok(commits)
+}
+
+/*
+ * Kahn's algorithm over the child -> parent edges: a commit becomes ready once all
+ * of its children were emitted. Date order picks the ready commit with the newest
+ * committer date, topo order keeps following the commit that was made ready last.
+ * */
+pub fn sort_commits(
+    commits: &HashMap<String, CommitContent>,
+    first_parent: bool,
+    order: LogOrder,
+) -> Vec<String> {
+    let mut children: HashMap<&str, usize> = commits.keys().map(|h| (h.as_str(), 0)).collect();
+    for commit in commits.values() {
+        for parent in parents_of(commit, first_parent) {
+            if let Some(count) = children.get_mut(parent.as_str()) {
+                *count += 1;
+            }
+        }
+    }
+
+    let key = |hash: &str| (commits[hash].committer.time, hash.to_string());
+    let mut tips: Vec<(i64, String)> = children
+        .iter()
+        .filter(|(_, &count)| count == 0)
+        .map(|(hash, _)| key(hash))
+        .collect();
+    tips.sort();
+
+    let mut sorted = Vec::with_capacity(commits.len());
+    match order {
+        LogOrder::Date => {
+            let mut ready: BinaryHeap<(i64, String)> = tips.into_iter().collect();
+            while let Some((_, hash)) = ready.pop() {
+                for parent in parents_of(&commits[&hash], first_parent) {
+                    if let Some(count) = children.get_mut(parent.as_str()) {
+                        *count -= 1;
+                        if *count == 0 {
+                            ready.push(key(parent));
+                        }
+                    }
+                }
+                sorted.push(hash);
+            }
+        }
+        LogOrder::Topo => {
+            let mut ready: Vec<String> = tips.into_iter().map(|(_, hash)| hash).collect();
+            while let Some(hash) = ready.pop() {
+                // pushed in reverse so the first parent is followed first
+                for parent in parents_of(&commits[&hash], first_parent).iter().rev() {
+                    if let Some(count) = children.get_mut(parent.as_str()) {
+                        *count -= 1;
+                        if *count == 0 {
+                            ready.push(parent.clone());
+                        }
+                    }
+                }
+                sorted.push(hash);
+            }
+        }
+    }
+    sorted
+}
+
+/*
+ * One lane per line of history that is still expected. A commit is drawn as '*' in
+ * its lane, then its lane is handed to its parents: merges open new lanes to the
+ * right ("\"), lanes that meet a commit already expected elsewhere close ("/").
+ * */
+struct Graph {
+    columns: Vec<String>,
+}
+
+impl Graph {
+    fn lanes(&self, highlight: Option<usize>) -> String {
+        (0..self.columns.len())
+            .map(|i| if Some(i) == highlight { "*" } else { "|" })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn commit_row(&mut self, hash: &str) -> (String, usize) {
+        let col = match self.columns.iter().position(|c| c == hash) {
+            Some(col) => col,
+            None => {
+                self.columns.push(hash.to_string());
+                self.columns.len() - 1
+            }
+        };
+        (self.lanes(Some(col)), col)
+    }
+
+    fn advance(&mut self, col: usize, parents: &[String]) -> Option<String> {
+        let old = self.columns.clone();
+        let mut new = old.clone();
+        new.remove(col);
+        let mut insert_at = col;
+        for parent in parents {
+            if !new.contains(parent) {
+                new.insert(insert_at, parent.clone());
+                insert_at += 1;
+            }
+        }
+
+        let mut line = vec![' '; old.len().max(new.len()) * 2];
+        let mut straight = true;
+        let mut mark = |from: usize, to: usize| {
+            if to == from {
+                line[2 * from] = '|';
+            } else if to > from {
+                line[2 * from + 1] = '\\';
+                straight = false;
+            } else {
+                line[2 * from - 1] = '/';
+                straight = false;
+            }
+        };
+        for (i, hash) in old.iter().enumerate() {
+            let targets: Vec<&String> = if i == col {
+                parents.iter().collect()
+            } else {
+                vec![hash]
+            };
+            for target in targets {
+                if let Some(j) = new.iter().position(|c| c == target) {
+                    mark(i, j);
+                }
+            }
+        }
+        self.columns = new;
+        if straight {
+            return None;
+        }
+        Some(line.into_iter().collect::<String>().trim_end().to_string())
+    }
+}
+
+fn decorations() -> Result<HashMap<String, Vec<String>>> {
+    let mut names: HashMap<String, Vec<String>> = HashMap::new();
+    let head = file_altering::get_curent_branch()?.trim().to_string();
+    for (name, hash) in refs::list_refs()? {
+        let label = if let Some(branch) = name.strip_prefix("refs/heads/") {
+            if name == head {
+                format!("HEAD -> {}", branch)
+            } else {
+                branch.to_string()
+            }
+        } else if let Some(tag) = name.strip_prefix("refs/tags/") {
+            format!("tag: {}", tag)
+        } else {
+            name.clone()
+        };
+        // annotated tags decorate the commit they tag
+        names.entry(revision::peel(&hash)?).or_default().push(label);
+    }
+    if !head.starts_with("refs/") && !head.is_empty() {
+        names.entry(head).or_default().insert(0, "HEAD".to_string());
+    }
+    for labels in names.values_mut() {
+        // "HEAD -> main" always comes first
+        labels.sort_by_key(|label| !label.starts_with("HEAD"));
+    }
+    Ok(names)
+}
+
+pub fn log_command(rev: Option<&str>, options: &LogOptions) -> Result<()> {
+    let (hide, start) = match rev {
+        Some(rev) => revision::resolve_range(rev)?,
+        None => (
+            None,
+            file_altering::get_head_commit()?
+                .ok_or_else(|| anyhow!("The current branch does not have any commits yet"))?,
+        ),
+    };
+    // for a..b everything reachable from a is hidden, through every parent
+    let hidden: HashSet<String> = match hide {
+        Some(hide) => collect_commits(&[hide], &HashSet::new(), false)?
+            .into_keys()
+            .collect(),
+        None => HashSet::new(),
+    };
+    let commits = collect_commits(&[start], &hidden, options.first_parent)?;
+    let mut sorted = sort_commits(&commits, options.first_parent, options.order);
+    if let Some(max) = options.max_count {
+        sorted.truncate(max);
+    }
+    let listed: HashSet<&String> = sorted.iter().collect();
+    let decorations = decorations()?;
+    let mut graph = Graph {
+        columns: Vec::new(),
+    };
+
+    for (n, hash) in sorted.iter().enumerate() {
+        let commit = &commits[hash];
+        let decoration = match decorations.get(hash) {
+            Some(labels) => format!(" ({})", labels.join(", ")).cyan().to_string(),
+            None => String::new(),
+        };
+        let (row, col) = if options.graph {
+            let (lanes, col) = graph.commit_row(hash);
+            (format!("{} ", lanes), col)
+        } else {
+            (String::new(), 0)
+        };
+
+        if options.oneline {
+            let subject = commit.message.lines().next().unwrap_or_default();
+            println!("{}{}{} {}", row, hash[..7].yellow(), decoration, subject);
+        } else {
+            if n > 0 && !options.graph {
+                println!();
+            }
+            println!(
+                "{}{}{}",
+                row,
+                format!("commit {}", hash).yellow(),
+                decoration
+            );
+        }
+
+        let before = graph.lanes(None);
+        let connector = if options.graph {
+            // parents hidden by a range or cut by --max-count get no lane
+            let shown: Vec<String> = parents_of(commit, options.first_parent)
+                .iter()
+                .filter(|parent| listed.contains(parent))
+                .cloned()
+                .collect();
+            graph.advance(col, &shown)
+        } else {
+            None
+        };
+        if options.oneline {
+            if let Some(connector) = &connector {
+                println!("{}", connector);
+            }
+            continue;
+        }
+
+        /*
+         * Lanes a merge opens are drawn right under its commit line, lanes that
+         * merge back are drawn after the body, which keeps the layout it had
+         * before them.
+         * */
+        let opens = connector
+            .as_ref()
+            .is_some_and(|connector| connector.contains('\\'));
+        let lanes = match &connector {
+            Some(_) if !opens => before,
+            _ => graph.lanes(None),
+        };
+        if opens {
+            println!("{}", connector.as_deref().unwrap_or_default());
+        }
+        let pad = if !options.graph || lanes.is_empty() {
+            String::new()
+        } else {
+            format!("{} ", lanes)
+        };
+        if commit.parents.len() > 1 {
+            let short: Vec<&str> = commit.parents.iter().map(|p| &p[..7]).collect();
+            println!("{}Merge: {}", pad, short.join(" "));
+        }
+        println!(
+            "{}Author: {} <{}>",
+            pad, commit.author.name, commit.author.email
+        );
+        println!("{}Date:   {}", pad, commit.author.format_date());
+        println!("{}", pad.trim_end());
+        for line in commit.message.lines() {
+            println!("{}    {}", pad, line);
+        }
+        match &connector {
+            Some(connector) if !opens => println!("{}", connector),
+            _ if options.graph && n + 1 < sorted.len() => println!("{}", pad.trim_end()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -1,44 +1,20 @@
-use crate::commands::checkout;
-use crate::commands::dec_object;
-use crate::commands::{commit, diff};
-use crate::others::file_altering;
+use crate::commands::checkout::{self, CheckoutMode};
+use crate::commands::{commit, diff, merge_base};
+use crate::objects::commit::SignatureOverrides;
+use crate::objects::store::{self, ObjectKind};
+use crate::objects::tree;
+use crate::others::diff3;
+use crate::others::{file_altering, index, refs, revision};
 use anyhow::Result;
 use colored::*;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io::Write;
-use std::path;
-
-pub fn get_commit_ancestors(commit_hash: &str) -> Result<String> {
-    let mut ancestors = String::new();
-    let mut to_visit = vec![commit_hash.to_string()];
-
-    while let Some(commit) = to_visit.pop() {
-        ancestors.push_str(&commit);
-        ancestors.push('\n');
-
-        // Get all parents of the current commit
-        let parents = file_altering::get_commit_parent(&commit)?;
-
-        // Add parents to the list of commits to visit
-        to_visit.extend(parents);
-    }
-
-    Ok(ancestors)
-}
+use std::path::{self, Path};
 
 pub fn fast_forward_verif(branch1: &str, branch2: &str) -> Result<()> {
-    let commit1 = file_altering::get_commit_from_branch(branch1)?;
-    let commit2 = file_altering::get_commit_from_branch(branch2)?;
-    let ancestors = get_commit_ancestors(&commit2)?;
-
-    let mut flag = false;
-    for line in ancestors.lines() {
-        if commit1 == line {
-            flag = true;
-        }
-    }
-    if flag {
+    let commit1 = revision::resolve_commit(branch1)?;
+    let commit2 = revision::resolve_commit(branch2)?;
+    if merge_base::is_ancestor(&commit1, &commit2)? {
         return Ok(());
     }
     Err(anyhow::anyhow!("Cannot perform fast_forward_merge!"))
@@ -71,9 +47,11 @@ pub fn check_for_conflicts(tree1: &str, tree2: &str) -> Result<String> {
         if let (Some(branch1), Some(branch2)) = (file1, file2) {
             if branch1.hash != branch2.hash {
                 conflicts.push_str(&file);
-                let content1 = dec_object::dec_obj(&branch1.hash)?;
-                let content2 = dec_object::dec_obj(&branch2.hash)?;
-                let diff_output = crate::commands::diff::diff_between_content(&content1, &content2);
+                let diff_output = diff::diff_between_blobs(
+                    Some(&branch1.hash),
+                    Some(&branch2.hash),
+                    &diff::DiffOptions::default(),
+                )?;
                 conflicts.push('\n');
                 conflicts.push_str(&diff_output);
             }
@@ -83,20 +61,28 @@ pub fn check_for_conflicts(tree1: &str, tree2: &str) -> Result<String> {
     Ok(conflicts)
 }
 
+// The merge happens in the working tree, so it has to be on the branch merged into.
+fn switch_to(branch: &str) -> Result<()> {
+    if file_altering::get_curent_branch()?.trim() != format!("refs/heads/{}", branch) {
+        checkout::checkout(branch)?;
+    }
+    Ok(())
+}
+
 pub fn fast_forward_merge(branch1: &str, branch2: &str, flag: bool) -> Result<()> {
-    let commit1 = file_altering::get_commit_from_branch(branch1)?;
-    let commit2 = file_altering::get_commit_from_branch(branch2)?;
+    switch_to(branch1)?;
+    let commit1 = revision::resolve_commit(branch1)?;
+    let commit2 = revision::resolve_commit(branch2)?;
     let diff_output = commit::detailed_print(&commit2, &commit1);
     let tree1 = file_altering::get_tree_from_commit(&commit1)?;
     let tree2 = file_altering::get_tree_from_commit(&commit2)?;
     if flag {
-        let branch_path = path::Path::new(".vcs")
-            .join("refs")
-            .join("heads")
-            .join(branch1);
-        let mut branch_file = fs::File::create(branch_path)?;
-        branch_file.write_all(&commit2.into_bytes())?;
-        checkout::checkout(branch1)?;
+        checkout::switch_trees(Some(&tree1), &tree2, CheckoutMode::Safe, branch2)?;
+        refs::update_ref(
+            &format!("refs/heads/{}", branch1),
+            &commit2,
+            &format!("merge {}: Fast-forward", branch2),
+        )?;
         return Ok(());
     }
     let conflicts = check_for_conflicts(&tree1, &tree2)?;
@@ -108,13 +94,12 @@ pub fn fast_forward_merge(branch1: &str, branch2: &str, flag: bool) -> Result<()
         );
         return Err(anyhow::anyhow!(output));
     }
-    let branch_path = path::Path::new(".vcs")
-        .join("refs")
-        .join("heads")
-        .join(branch1);
-    let mut branch_file = fs::File::create(branch_path)?;
-    branch_file.write_all(&commit2.into_bytes())?;
-    checkout::checkout(branch1)?;
+    checkout::switch_trees(Some(&tree1), &tree2, CheckoutMode::Safe, branch2)?;
+    refs::update_ref(
+        &format!("refs/heads/{}", branch1),
+        &commit2,
+        &format!("merge {}: Fast-forward", branch2),
+    )?;
     println!(
         "<<<<<<<<<<{}>>>>>>>>>> <<<<<<<<<<{}>>>>>>>>>> \n {} \n",
         branch1.magenta().bold(),
@@ -132,35 +117,344 @@ pub fn fast_forward_merge(branch1: &str, branch2: &str, flag: bool) -> Result<()
  * I will resolve this conflict by keeping everytime the file in the branch we merge to.
  * */
 
+// With several best common ancestors (criss-cross merges) the newest one is used.
 pub fn find_common_ancestor(branch1: &str, branch2: &str) -> Result<String> {
-    let commit1 = file_altering::get_commit_from_branch(branch1)?;
-    let commit2 = file_altering::get_commit_from_branch(branch2)?;
-    let anc1 = get_commit_ancestors(&commit1)?;
-    let anc2 = get_commit_ancestors(&commit2)?;
+    let commit1 = revision::resolve_commit(branch1)?;
+    let commit2 = revision::resolve_commit(branch2)?;
+    merge_base::merge_bases(&commit1, &commit2)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("No common ancestor found"))
+}
+
+/*
+ * Kinds of conflicts a tree merge can leave behind:
+ *     content        both sides edited the same lines of a file
+ *     modify/delete  one side edited a file the other side deleted
+ *     binary         both sides changed a binary file
+ * Conflicted paths are recorded in the index as stages, 1 for the base version,
+ * 2 for ours and 3 for theirs, a missing side has no stage.
+ * */
+pub struct TreeMerge {
+    pub index: index::Index,
+    pub conflicts: Vec<(String, &'static str)>,
+    pub contents: HashMap<String, Vec<u8>>, // working tree content of the conflicted files
+}
+
+fn tree_entries(tree_hash: &str) -> Result<HashMap<String, (String, u32)>> {
+    Ok(file_altering::build_index_from_tree(tree_hash)?
+        .obj
+        .into_iter()
+        .map(|obj| {
+            let path = obj.path.to_str().unwrap().to_string();
+            (path, (obj.hash, tree::file_mode(obj.permissions)))
+        })
+        .collect())
+}
+
+fn read_blob(entry: Option<&(String, u32)>) -> Result<Vec<u8>> {
+    match entry {
+        Some((hash, _)) => store::read_object_of(hash, ObjectKind::Blob),
+        None => Ok(Vec::new()),
+    }
+}
+
+/*
+ * Merges the trees of ours and theirs against their common base, file by file.
+ * A file changed on one side only takes that side, a file changed on both sides
+ * goes through a line level three-way merge. With favor_theirs, conflicting
+ * hunks, binary files and modify/delete conflicts take their side.
+ * */
+pub fn merge_trees(
+    base_tree: &str,
+    ours_tree: &str,
+    theirs_tree: &str,
+    labels: (&str, &str),
+    favor_theirs: bool,
+) -> Result<TreeMerge> {
+    let base = tree_entries(base_tree)?;
+    let ours = tree_entries(ours_tree)?;
+    let theirs = tree_entries(theirs_tree)?;
+    let all_files: BTreeSet<&String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+
+    let mut merged = TreeMerge {
+        index: index::Index::new(),
+        conflicts: Vec::new(),
+        contents: HashMap::new(),
+    };
+    let mut keep = |path: &str, entry: Option<&(String, u32)>, stage: u8| {
+        if let Some((hash, mode)) = entry {
+            let mut obj = index::ObjectInfo::from_tree_entry(Path::new(path), hash, *mode);
+            obj.stage = stage;
+            merged.index.add_object(obj);
+        }
+    };
+
+    let mut conflicts = Vec::new();
+    let mut contents = HashMap::new();
+    for path in all_files {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        if o == t || b == t {
+            keep(path, o, 0);
+            continue;
+        }
+        if b == o {
+            keep(path, t, 0);
+            continue;
+        }
+        let (Some(ours_entry), Some(theirs_entry)) = (o, t) else {
+            // modify/delete, the edited version stays in the working tree
+            if favor_theirs {
+                keep(path, t, 0);
+            } else {
+                keep(path, b, 1);
+                keep(path, o, 2);
+                keep(path, t, 3);
+                conflicts.push((path.clone(), "modify/delete"));
+                contents.insert(path.clone(), read_blob(o.or(t))?);
+            }
+            continue;
+        };
+
+        // only one side changed the mode, that change wins
+        let mode = if b.map(|b| b.1) == Some(ours_entry.1) {
+            theirs_entry.1
+        } else {
+            ours_entry.1
+        };
+        let (base_content, ours_content, theirs_content) =
+            (read_blob(b)?, read_blob(o)?, read_blob(t)?);
+        let binary = [&base_content, &ours_content, &theirs_content]
+            .iter()
+            .any(|content| diff::is_binary(content));
+        let result = if binary {
+            None
+        } else {
+            // none of the contents is binary, so they are all valid UTF-8
+            Some(diff3::merge(
+                &String::from_utf8_lossy(&base_content),
+                &String::from_utf8_lossy(&ours_content),
+                &String::from_utf8_lossy(&theirs_content),
+                labels.0,
+                labels.1,
+                favor_theirs,
+            ))
+        };
+
+        match result {
+            Some(result) if result.conflicts == 0 => {
+                let hash = store::write_object(ObjectKind::Blob, result.content.as_bytes())?;
+                keep(path, Some(&(hash, mode)), 0);
+            }
+            None if favor_theirs => keep(path, t, 0),
+            _ => {
+                keep(path, b, 1);
+                keep(path, o, 2);
+                keep(path, t, 3);
+                let (kind, content) = match result {
+                    Some(result) => ("content", result.content.into_bytes()),
+                    None => ("binary", ours_content),
+                };
+                conflicts.push((path.clone(), kind));
+                contents.insert(path.clone(), content);
+            }
+        }
+    }
+    merged.conflicts = conflicts;
+    merged.contents = contents;
+    Ok(merged)
+}
+
+// Paths whose working file the merge result changes, compared to our tree.
+pub fn touched_paths(ours_tree: &str, merged: &TreeMerge) -> Result<Vec<String>> {
+    let ours = tree_entries(ours_tree)?;
+    let mut paths: BTreeSet<String> = merged.contents.keys().cloned().collect();
+    let mut kept = HashSet::new();
+    for obj in merged.index.obj.iter().filter(|obj| obj.stage == 0) {
+        let path = obj.path.to_str().unwrap().to_string();
+        if ours.get(&path).map(|(hash, _)| hash) != Some(&obj.hash) {
+            paths.insert(path.clone());
+        }
+        kept.insert(path);
+    }
+    for path in ours.keys() {
+        if !kept.contains(path) {
+            paths.insert(path.clone());
+        }
+    }
+    Ok(paths.into_iter().collect())
+}
 
-    for line1 in anc1.lines() {
-        for line2 in anc2.lines() {
-            if line1 == line2 {
-                return Ok(line1.to_string());
+/*
+ * Puts a conflicted merge in the working tree: merged files that changed are
+ * written, conflicted files get their content with markers and files of our
+ * tree that did not survive the merge are removed. The index gets the merged
+ * entries and the stages of the conflicts.
+ * */
+pub fn write_merge_result(ours_tree: &str, merged: &TreeMerge) -> Result<()> {
+    for path in touched_paths(ours_tree, merged)? {
+        // a conflicted file takes the mode of ours, or theirs, before the base one
+        let mut entries: Vec<&index::ObjectInfo> = merged
+            .index
+            .obj
+            .iter()
+            .filter(|obj| obj.path.to_str() == Some(path.as_str()))
+            .collect();
+        entries.sort_by_key(|obj| obj.stage == 1);
+        let content = match (merged.contents.get(&path), entries.first()) {
+            (Some(content), _) => content.clone(),
+            (None, Some(obj)) => store::read_object_of(&obj.hash, ObjectKind::Blob)?,
+            (None, None) => {
+                file_altering::remove_working_file(Path::new(&path))?;
+                continue;
             }
+        };
+        let mode = entries
+            .first()
+            .map_or(tree::MODE_FILE, |obj| obj.permissions);
+        file_altering::write_working_file(Path::new(&path), &content, mode)?;
+    }
+    merged.index.save_index_file_truncate()?;
+    Ok(())
+}
+
+/*
+ * A merge that stopped on conflicts leaves its state in .vcs:
+ *     MERGE_HEAD  the commit being merged in
+ *     ORIG_HEAD   our commit before the merge, restored by --abort
+ *     MERGE_MSG   the message of the merge commit
+ * */
+fn merge_state_path(name: &str) -> path::PathBuf {
+    Path::new(".vcs").join(name)
+}
+
+pub fn merge_head() -> Result<Option<String>> {
+    let merge_head = merge_state_path("MERGE_HEAD");
+    if !merge_head.exists() {
+        return Ok(None);
+    }
+    Ok(Some(fs::read_to_string(merge_head)?.trim().to_string()))
+}
+
+fn write_merge_state(ours: &str, theirs: &str, message: &str) -> Result<()> {
+    fs::write(merge_state_path("MERGE_HEAD"), theirs)?;
+    fs::write(merge_state_path("ORIG_HEAD"), ours)?;
+    fs::write(merge_state_path("MERGE_MSG"), message)?;
+    Ok(())
+}
+
+pub fn clear_merge_state() -> Result<()> {
+    for name in ["MERGE_HEAD", "MERGE_MSG"] {
+        let state = merge_state_path(name);
+        if state.exists() {
+            fs::remove_file(state)?;
         }
     }
-    Err(anyhow::anyhow!("No common ancestor found"))
+    Ok(())
 }
 
-use regex::Regex;
+fn ensure_no_merge_in_progress() -> Result<()> {
+    if merge_head()?.is_some() {
+        return Err(anyhow::anyhow!(
+            "A merge is in progress, finish it with `rvcs merge --continue` or `rvcs merge --abort`"
+        ));
+    }
+    Ok(())
+}
 
-fn strip_ansi_codes(input: &str) -> String {
-    // Regex to match ANSI escape codes
-    let ansi_regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
-    ansi_regex.replace_all(input, "").to_string()
+// Creates the merge commit once every conflict was resolved and added.
+pub fn merge_continue(message: Option<&str>, overrides: &SignatureOverrides) -> Result<()> {
+    let theirs = merge_head()?.ok_or_else(|| anyhow::anyhow!("There is no merge to continue"))?;
+    let index = index::Index::load()?;
+    let unmerged = index.unmerged_paths();
+    if !unmerged.is_empty() {
+        let mut output = String::from("Fix the conflicts and `rvcs add` the files first:");
+        for path in unmerged {
+            output.push_str(&format!("\n\t{}", path.display()));
+        }
+        return Err(anyhow::anyhow!(output));
+    }
+
+    let ours = revision::resolve_commit("HEAD")?;
+    let message = match message {
+        Some(message) => message.to_string(),
+        None => fs::read_to_string(merge_state_path("MERGE_MSG"))?
+            .trim()
+            .to_string(),
+    };
+    let merged_tree = tree::Tree::new_tree_from_index(index)?;
+    merged_tree.create_tree()?;
+    commit::create_merge_commit(&ours, &theirs, &merged_tree.id, &message, overrides)?;
+    clear_merge_state()
+}
+
+/*
+ * Puts the paths back to their version in the tree, in the index and the
+ * working tree, removing the ones the tree does not have. Every other entry
+ * and file is left alone.
+ * */
+pub fn restore_paths(tree_hash: &str, paths: &BTreeSet<path::PathBuf>) -> Result<()> {
+    let mut current = index::Index::load()?;
+    let mut target = file_altering::build_index_from_tree(tree_hash)?;
+    target.obj.retain(|obj| paths.contains(&obj.path));
+    current.obj.retain(|obj| !paths.contains(&obj.path));
+    for path in paths {
+        if !target.obj.iter().any(|obj| &obj.path == path) {
+            file_altering::remove_working_file(path)?;
+        }
+    }
+    for obj in target.obj {
+        let content = store::read_object_of(&obj.hash, ObjectKind::Blob)?;
+        file_altering::write_working_file(&obj.path, &content, obj.permissions)?;
+        current.add_object(obj);
+    }
+    current.save_index_file_truncate()
+}
+
+/*
+ * Throws the conflicted merge away. Only the paths the merge wrote go back to
+ * ORIG_HEAD, that is the paths it touched and the ones still unmerged, other
+ * local changes stay as they are.
+ * */
+pub fn merge_abort() -> Result<()> {
+    let theirs = merge_head()?.ok_or_else(|| anyhow::anyhow!("There is no merge to abort"))?;
+    let orig_head = fs::read_to_string(merge_state_path("ORIG_HEAD"))?
+        .trim()
+        .to_string();
+    let orig_tree = file_altering::get_tree_from_commit(&orig_head)?;
+    // the merge is computed again to know which paths it wrote
+    let base_tree =
+        file_altering::get_tree_from_commit(&find_common_ancestor(&orig_head, &theirs)?)?;
+    let theirs_tree = file_altering::get_tree_from_commit(&theirs)?;
+    let merged = merge_trees(
+        &base_tree,
+        &orig_tree,
+        &theirs_tree,
+        ("HEAD", &theirs),
+        false,
+    )?;
+    let mut paths: BTreeSet<path::PathBuf> = touched_paths(&orig_tree, &merged)?
+        .into_iter()
+        .map(path::PathBuf::from)
+        .collect();
+    paths.extend(index::Index::load()?.unmerged_paths());
+    restore_paths(&orig_tree, &paths)?;
+    clear_merge_state()?;
+    println!("Merge aborted, back at {}", &orig_head[..7]);
+    Ok(())
 }
 
 pub fn three_way_merge(branch1: &str, branch2: &str, flag: bool) -> Result<()> {
+    ensure_no_merge_in_progress()?;
+    switch_to(branch1)?;
     // find common ancestors and get all the commits
     let commit_anc = find_common_ancestor(branch1, branch2)?;
-    let commit1 = file_altering::get_commit_from_branch(branch1)?;
-    let commit2 = file_altering::get_commit_from_branch(branch2)?;
+    let commit1 = revision::resolve_commit(branch1)?;
+    let commit2 = revision::resolve_commit(branch2)?;
     // get the trees form the commits
     let diff_output1 = commit::detailed_print(&commit1, &commit_anc);
     let diff_output2 = commit::detailed_print(&commit2, &commit_anc);
@@ -168,94 +462,35 @@ pub fn three_way_merge(branch1: &str, branch2: &str, flag: bool) -> Result<()> {
     let tree1 = file_altering::get_tree_from_commit(&commit1)?;
     let tree2 = file_altering::get_tree_from_commit(&commit2)?;
 
-    if !flag {
-        let conflicts1 = check_for_conflicts(&tree1, &common_tree)?;
-        let mut conflicts2 = check_for_conflicts(&tree2, &common_tree)?;
-        if conflicts2.is_empty() {
-            let temp1 = diff::diff_between_commits(&commit_anc, &commit2)?;
-            let temp2 = diff::diff_between_commits(&commit_anc, &commit1)?;
-            let sections1 = commit::get_sections(&temp1)?;
-            let sections2 = commit::get_sections(&temp2)?;
-
-            let mut files1 = Vec::new();
-            let mut files2 = Vec::new();
-            for section in sections1.iter() {
-                for line in section.iter() {
-                    if line.starts_with("Added") {
-                        let file_name = line["Added file: ".len()..].to_string();
-                        files1.push(file_name);
-                    }
-                }
-            }
-            for section in sections2.iter() {
-                for line in section.iter() {
-                    if line.starts_with("Added") {
-                        let file_name = line["Added file: ".len()..].to_string();
-                        files2.push(file_name);
-                    }
-                }
-            }
-            for filename1 in files1.iter() {
-                for filename2 in files2.iter() {
-                    if filename1 == filename2 {
-                        // i need to get the content of this files and than diff them
-                        let index1 = file_altering::build_index_from_tree(&tree1)?;
-                        let index2 = file_altering::build_index_from_tree(&tree2)?;
-                        let file1 = index1.obj.iter().find(|obj| {
-                            let t1 = strip_ansi_codes(filename1);
-                            obj.path.to_str().unwrap() == t1
-                        });
-                        let file2 = index2.obj.iter().find(|obj| {
-                            let t2 = strip_ansi_codes(filename2);
-                            obj.path.to_str().unwrap() == t2
-                        });
-                        if let (Some(branch1), Some(branch2)) = (file1, file2) {
-                            if branch1.hash != branch2.hash {
-                                conflicts2.push_str(filename1);
-                                let content1 = dec_object::dec_obj(&branch1.hash)?;
-                                let content2 = dec_object::dec_obj(&branch2.hash)?;
-                                let diff_output = crate::commands::diff::diff_between_content(
-                                    &content1, &content2,
-                                );
-                                conflicts2.push('\n');
-                                conflicts2.push_str(&diff_output);
-                                conflicts2.push('\n');
-                            }
-                        }
-                    }
-                }
-            }
-        }
-        match (conflicts1.is_empty(), conflicts2.is_empty()) {
-            (false, false) => {
-                let output = format!(
-                    "Conflicts in files: \n {} \n {} \n Conflicts are generated the two branches",
-                    conflicts1.red(),
-                    conflicts2.red()
-                );
-                return Err(anyhow::anyhow!(output));
-            }
-            (true, false) => {
-                let output = format!(
-                    "Conflicts in files: \n {} \n {} \n Conflicts are generated by second branch",
-                    conflicts1.green(),
-                    conflicts2.red()
-                );
-                return Err(anyhow::anyhow!(output));
-            }
-            (false, true) => {
-                let output = format!(
-                    "Conflicts in files: \n {} \n {} \n Conflicts are generated by first branch",
-                    conflicts1.green(),
-                    conflicts2.red()
-                );
-                return Err(anyhow::anyhow!(output));
-            }
-            _ => {}
+    let message = format!("Merge branch '{}' into {}", branch2, branch1);
+    let merged = merge_trees(&common_tree, &tree1, &tree2, (branch1, branch2), flag)?;
+    checkout::ensure_unchanged(&tree1, &touched_paths(&tree1, &merged)?)?;
+    if !merged.conflicts.is_empty() {
+        write_merge_result(&tree1, &merged)?;
+        write_merge_state(&commit1, &commit2, &message)?;
+        for (path, kind) in &merged.conflicts {
+            println!(
+                "{} ({}): Merge conflict in {}",
+                "CONFLICT".red().bold(),
+                kind,
+                path
+            );
         }
+        return Err(anyhow::anyhow!(
+            "Automatic merge failed, fix the conflicts, `rvcs add` them and run `rvcs merge --continue`."
+        ));
     }
-    commit::create_merge_commit(&commit1, &commit2)?;
-    checkout::checkout(branch1)?;
+
+    let merged_tree = tree::Tree::new_tree_from_index(merged.index)?;
+    merged_tree.create_tree()?;
+    checkout::switch_trees(Some(&tree1), &merged_tree.id, CheckoutMode::Safe, branch2)?;
+    commit::create_merge_commit(
+        &commit1,
+        &commit2,
+        &merged_tree.id,
+        &message,
+        &SignatureOverrides::default(),
+    )?;
     println!(
         "<<<<<<<<<<{}>>>>>>>>>> \n {} \n <<<<<<<<<<{}>>>>>>>>>> \n {}",
         branch1.magenta().bold(),
@@ -267,8 +502,13 @@ pub fn three_way_merge(branch1: &str, branch2: &str, flag: bool) -> Result<()> {
 }
 
 pub fn merge(branch: &str, flag: bool) -> Result<()> {
-    let current_branch = file_altering::get_curent_branch()?;
-    let branch_name = &current_branch["refs/heads/".len()..];
+    ensure_no_merge_in_progress()?;
+    let Some(current_branch) = file_altering::get_head_branch()? else {
+        return Err(anyhow::anyhow!(
+            "HEAD is detached, checkout the branch to merge into first"
+        ));
+    };
+    let branch_name = current_branch.as_str();
     match fast_forward_verif(branch_name, branch) {
         Ok(_) => {
             println!("Performing fast forward merge!");
@@ -0,0 +1,149 @@
+use crate::objects::commit::{Commit, CommitContent};
+use crate::others::revision;
+use anyhow::Result;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+const PARENT1: u8 = 1;
+const PARENT2: u8 = 2;
+const STALE: u8 = 4;
+const RESULT: u8 = 8;
+
+// Reads every commit once, the walks below visit shared history a lot.
+#[derive(Default)]
+struct CommitCache {
+    commits: HashMap<String, CommitContent>,
+}
+
+impl CommitCache {
+    fn get(&mut self, hash: &str) -> Result<&CommitContent> {
+        if !self.commits.contains_key(hash) {
+            self.commits.insert(hash.to_string(), Commit::read(hash)?);
+        }
+        Ok(&self.commits[hash])
+    }
+
+    fn time(&mut self, hash: &str) -> Result<i64> {
+        Ok(self.get(hash)?.committer.time)
+    }
+}
+
+/*
+ * Walks down from both commits newest first, painting every commit with the
+ * side(s) it is reachable from. A commit painted by both sides is a common
+ * ancestor, everything below it is marked stale since it can never be the
+ * lowest one. The walk stops once only stale commits are left.
+ * */
+fn paint_down_to_common(one: &str, two: &str, cache: &mut CommitCache) -> Result<Vec<String>> {
+    let mut flags: HashMap<String, u8> = HashMap::new();
+    let mut queue: BinaryHeap<(i64, String)> = BinaryHeap::new();
+    flags.insert(one.to_string(), PARENT1);
+    *flags.entry(two.to_string()).or_default() |= PARENT2;
+    queue.push((cache.time(one)?, one.to_string()));
+    queue.push((cache.time(two)?, two.to_string()));
+
+    let mut results = Vec::new();
+    while queue.iter().any(|(_, hash)| flags[hash] & STALE == 0) {
+        let Some((_, hash)) = queue.pop() else {
+            break;
+        };
+        let mut paint = flags[&hash] & (PARENT1 | PARENT2 | STALE);
+        if paint & (PARENT1 | PARENT2) == PARENT1 | PARENT2 {
+            if flags[&hash] & RESULT == 0 {
+                *flags.get_mut(&hash).unwrap() |= RESULT;
+                results.push(hash.clone());
+            }
+            paint |= STALE;
+        }
+        for parent in cache.get(&hash)?.parents.clone() {
+            let parent_flags = flags.entry(parent.clone()).or_default();
+            if *parent_flags & paint == paint {
+                continue;
+            }
+            *parent_flags |= paint;
+            queue.push((cache.time(&parent)?, parent));
+        }
+    }
+
+    // a result reached from a newer result was found too early
+    Ok(results
+        .into_iter()
+        .filter(|hash| flags[hash] & STALE == 0)
+        .collect())
+}
+
+// Every commit reachable from the start, the start included.
+fn ancestors(start: &str, cache: &mut CommitCache) -> Result<HashSet<String>> {
+    let mut seen = HashSet::new();
+    let mut to_visit = vec![start.to_string()];
+    while let Some(hash) = to_visit.pop() {
+        if seen.insert(hash.clone()) {
+            to_visit.extend(cache.get(&hash)?.parents.iter().cloned());
+        }
+    }
+    Ok(seen)
+}
+
+/*
+ * The best common ancestors of two commits: common ancestors that are not an
+ * ancestor of another common ancestor. Criss-cross histories have several,
+ * they are returned newest first.
+ * */
+pub fn merge_bases(one: &str, two: &str) -> Result<Vec<String>> {
+    if one == two {
+        return Ok(vec![one.to_string()]);
+    }
+    let mut cache = CommitCache::default();
+    let candidates = paint_down_to_common(one, two, &mut cache)?;
+    if candidates.len() <= 1 {
+        return Ok(candidates);
+    }
+
+    // clock skew can let an ancestor of another candidate through
+    let mut redundant = HashSet::new();
+    for candidate in &candidates {
+        if redundant.contains(candidate) {
+            continue;
+        }
+        for ancestor in ancestors(candidate, &mut cache)? {
+            if &ancestor != candidate {
+                redundant.insert(ancestor);
+            }
+        }
+    }
+    let mut bases: Vec<String> = candidates
+        .into_iter()
+        .filter(|hash| !redundant.contains(hash))
+        .collect();
+    bases.sort_by_key(|hash| std::cmp::Reverse(cache.commits[hash].committer.time));
+    Ok(bases)
+}
+
+// True when `ancestor` can be reached from `descendant`, a commit is its own ancestor.
+pub fn is_ancestor(ancestor: &str, descendant: &str) -> Result<bool> {
+    Ok(merge_bases(ancestor, descendant)?
+        .iter()
+        .any(|base| base == ancestor))
+}
+
+// Returns false when there is nothing to print, the process then exits with 1 like git.
+pub fn merge_base_command(
+    rev1: &str,
+    rev2: &str,
+    all: bool,
+    is_ancestor_check: bool,
+) -> Result<bool> {
+    let commit1 = revision::resolve_commit(rev1)?;
+    let commit2 = revision::resolve_commit(rev2)?;
+    if is_ancestor_check {
+        return is_ancestor(&commit1, &commit2);
+    }
+    let bases = merge_bases(&commit1, &commit2)?;
+    if bases.is_empty() {
+        return Ok(false);
+    }
+    let shown = if all { &bases[..] } else { &bases[..1] };
+    for base in shown {
+        println!("{}", base);
+    }
+    Ok(true)
+}
@@ -0,0 +1,78 @@
+use crate::commands::rm;
+use crate::others::{file_altering, index};
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/*
+ * Moves a tracked file or directory in the working tree and renames its index
+ * entries, so the move is staged as one. A destination that is an existing
+ * directory receives the source under its own name.
+ * */
+pub fn mv_command(source: &str, destination: &str) -> Result<()> {
+    let source = index::normalize_path(Path::new(source));
+    let mut destination = index::normalize_path(Path::new(destination));
+    if destination.is_dir() {
+        let name = source
+            .file_name()
+            .ok_or_else(|| anyhow!("Cannot move '{}'", source.display()))?;
+        destination = destination.join(name);
+    }
+
+    let mut current = index::Index::load()?;
+    let entries = rm::tracked_under(&current, &source);
+    if entries.is_empty() {
+        return Err(anyhow!("'{}' is not tracked.", source.display()));
+    }
+    if entries.iter().any(|obj| obj.stage != 0) {
+        return Err(anyhow!(
+            "'{}' has merge conflicts, resolve them first.",
+            source.display()
+        ));
+    }
+    if !source.exists() {
+        return Err(anyhow!("'{}' does not exist.", source.display()));
+    }
+    if destination.exists() {
+        return Err(anyhow!("'{}' already exists.", destination.display()));
+    }
+    if destination.starts_with(&source) {
+        return Err(anyhow!("Cannot move '{}' into itself.", source.display()));
+    }
+    if current
+        .obj
+        .iter()
+        .any(|obj| obj.path.starts_with(&destination))
+    {
+        return Err(anyhow!("'{}' is already tracked.", destination.display()));
+    }
+
+    if let Some(parent) = destination.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    fs::rename(&source, &destination).with_context(|| {
+        format!(
+            "Failed to move '{}' to '{}'",
+            source.display(),
+            destination.display()
+        )
+    })?;
+    // prunes the directories the move left empty
+    file_altering::remove_working_file(&source)?;
+
+    for obj in current.obj.iter_mut() {
+        if let Ok(rest) = obj.path.strip_prefix(&source) {
+            let moved: PathBuf = if rest.as_os_str().is_empty() {
+                destination.clone()
+            } else {
+                destination.join(rest)
+            };
+            obj.path = moved;
+        }
+    }
+    current.save_index_file_truncate()?;
+    Ok(())
+}
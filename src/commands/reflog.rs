@@ -0,0 +1,24 @@
+use crate::others::reflog;
+use anyhow::anyhow;
+use anyhow::Result;
+use colored::*;
+
+// Newest first, numbered like the <ref>@{n} revisions that name them, with who moved the ref and when.
+pub fn reflog_command(name: Option<&str>) -> Result<()> {
+    let name = name.unwrap_or("HEAD");
+    let entries = reflog::read(&reflog::log_name(name)?)?;
+    if entries.is_empty() {
+        return Err(anyhow!("'{}' has no log", name));
+    }
+    for (n, entry) in entries.iter().rev().enumerate() {
+        println!(
+            "{} {}@{{{}}}: {} {}",
+            entry.new[..7].yellow(),
+            name,
+            n,
+            entry.reason,
+            format!("({}, {})", entry.who.name, entry.who.format_date()).dimmed()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use crate::objects::pack::{self, PackObject};
+use crate::objects::store;
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fs;
+
+pub fn repack_command(
+    all: bool,
+    delete_redundant: bool,
+    window: usize,
+    depth: usize,
+) -> Result<()> {
+    let loose = store::loose_objects()?;
+    let old_packs = if all { pack::list_packs()? } else { Vec::new() };
+
+    let mut seen = HashSet::new();
+    let mut objects = Vec::new();
+    let mut hashes: Vec<String> = loose.clone();
+    for idx_path in &old_packs {
+        hashes.extend(pack::PackIndex::open(idx_path)?.hashes());
+    }
+    for hash in hashes {
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        let (kind, payload) = store::read_object(&hash)?;
+        objects.push(PackObject {
+            hash,
+            kind,
+            payload,
+        });
+    }
+
+    if objects.is_empty() {
+        println!("Nothing new to pack.");
+        return Ok(());
+    }
+
+    let pack_path = pack::write_pack(&objects, window, depth)?;
+
+    pack::verify_objects(&pack_path, &objects)?;
+
+    let deltas = pack::delta_count(&pack_path)?;
+    println!(
+        "Packed {} objects ({} deltas) into {}",
+        objects.len(),
+        deltas,
+        pack_path.display()
+    );
+
+    if delete_redundant {
+        for hash in &loose {
+            store::remove_loose_object(hash)?;
+        }
+        for idx_path in &old_packs {
+            if idx_path.with_extension("pack") == pack_path {
+                continue;
+            }
+            fs::remove_file(idx_path)?;
+            fs::remove_file(idx_path.with_extension("pack"))?;
+        }
+        println!(
+            "Removed {} loose objects and {} old packs.",
+            loose.len(),
+            old_packs
+                .iter()
+                .filter(|idx| idx.with_extension("pack") != pack_path)
+                .count()
+        );
+    }
+    Ok(())
+}
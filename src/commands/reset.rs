@@ -0,0 +1,119 @@
+use crate::commands::checkout::{self, CheckoutMode};
+use crate::commands::merge;
+use crate::objects::commit::Commit;
+use crate::others::{file_altering, index, revision};
+use anyhow::anyhow;
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/*
+ * How far a reset goes after moving the current branch:
+ *     Soft   the index and the working tree are left alone
+ *     Mixed  the index is rebuilt from the target tree, local changes stay
+ *     Hard   the working tree is overwritten with the target tree as well
+ * */
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ResetMode {
+    Soft,
+    Mixed,
+    Hard,
+}
+
+/*
+ * `rvcs reset [<rev>] [<path>...]`: the first argument is a revision when it
+ * names a commit, anything after it, or everything when it does not, is a path
+ * to unstage.
+ * */
+pub fn reset_command(args: &[String], mode: Option<ResetMode>) -> Result<()> {
+    let (rev, paths) = match args.split_first() {
+        Some((first, rest)) if revision::resolve_commit(first).is_ok() => {
+            (Some(first.as_str()), rest)
+        }
+        _ => (None, args),
+    };
+    if paths.is_empty() {
+        return reset_head(rev.unwrap_or("HEAD"), mode.unwrap_or(ResetMode::Mixed));
+    }
+    if mode.is_some_and(|mode| mode != ResetMode::Mixed) {
+        return Err(anyhow!("Paths can only be reset with --mixed."));
+    }
+    reset_paths(rev, paths)
+}
+
+// Moves the current branch, or a detached HEAD, to the commit.
+pub fn reset_head(rev: &str, mode: ResetMode) -> Result<()> {
+    let previous = file_altering::get_head_commit()?.ok_or_else(|| {
+        anyhow!("The current branch has no commits yet, there is nothing to reset.")
+    })?;
+    if mode == ResetMode::Soft && merge::merge_head()?.is_some() {
+        return Err(anyhow!("Cannot do a soft reset in the middle of a merge."));
+    }
+    let target = revision::resolve_commit(rev)?;
+    let target_tree = file_altering::get_tree_from_commit(&target)?;
+    match mode {
+        ResetMode::Soft => {}
+        ResetMode::Mixed => {
+            file_altering::build_index_from_tree(&target_tree)?.save_index_file_truncate()?
+        }
+        ResetMode::Hard => {
+            let previous_tree = file_altering::get_tree_from_commit(&previous)?;
+            checkout::switch_trees(Some(&previous_tree), &target_tree, CheckoutMode::Force, rev)?;
+        }
+    }
+    if mode != ResetMode::Soft {
+        merge::clear_merge_state()?;
+    }
+    fs::write(Path::new(".vcs").join("ORIG_HEAD"), &previous)?;
+    file_altering::update_head(&target, &format!("reset: moving to {}", rev))?;
+    if mode == ResetMode::Hard {
+        let message = Commit::read(&target)?.message;
+        println!(
+            "HEAD is now at {} {}",
+            &target[..7],
+            message.lines().next().unwrap_or("")
+        );
+    }
+    Ok(())
+}
+
+/*
+ * Puts the entries of the paths back to their version in the revision, HEAD by
+ * default. Paths the revision does not have are removed from the index, a
+ * directory stands for every file under it. The working tree is not touched.
+ * */
+pub fn reset_paths(rev: Option<&str>, paths: &[String]) -> Result<()> {
+    let source = match rev {
+        Some(rev) => file_altering::build_index_from_tree(&revision::resolve_tree(rev)?)?,
+        None => match file_altering::get_head_commit()? {
+            Some(commit) => file_altering::build_index_from_tree(
+                &file_altering::get_tree_from_commit(&commit)?,
+            )?,
+            None => index::Index::new(),
+        },
+    };
+    let paths: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| index::normalize_path(Path::new(path)))
+        .collect();
+    let selected = |path: &Path| paths.iter().any(|selected| path.starts_with(selected));
+
+    let mut current = index::Index::load()?;
+    for path in &paths {
+        let known = |obj: &index::ObjectInfo| obj.path.starts_with(path);
+        if !current.obj.iter().any(known) && !source.obj.iter().any(known) {
+            return Err(anyhow!(
+                "'{}' did not match any file known to rvcs.",
+                path.display()
+            ));
+        }
+    }
+    current.obj.retain(|obj| !selected(&obj.path));
+    for obj in source.obj {
+        if selected(&obj.path) {
+            current.add_object(obj);
+        }
+    }
+    current.save_index_file_truncate()?;
+    Ok(())
+}
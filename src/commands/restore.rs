@@ -0,0 +1,94 @@
+use crate::objects::store::{self, ObjectKind};
+use crate::others::{file_altering, index, revision};
+use anyhow::anyhow;
+use anyhow::Result;
+use glob::Pattern;
+use std::path::Path;
+
+/*
+ * A pathspec names a file, every file under a directory ("." is all of them)
+ * or the files matching a glob such as "*.txt".
+ * */
+fn matches(pathspec: &str, path: &Path) -> bool {
+    let spec = index::normalize_path(Path::new(pathspec));
+    if path.starts_with(&spec) {
+        return true;
+    }
+    Pattern::new(pathspec)
+        .map(|pattern| pattern.matches_path(path))
+        .unwrap_or(false)
+}
+
+/*
+ * Puts back the version of the paths found in the source. The working tree is
+ * restored by default and the index with --staged, both with the two flags.
+ * Without --source, the working tree is restored from the index and the index
+ * from HEAD. Tracked files the source does not have are removed.
+ * */
+pub fn restore_command(
+    pathspecs: &[String],
+    source: Option<&str>,
+    staged: bool,
+    worktree: bool,
+) -> Result<()> {
+    let worktree = worktree || !staged;
+    let from_index = source.is_none() && !staged;
+    let mut current = index::Index::load()?;
+    let source = match source {
+        Some(rev) => file_altering::build_index_from_tree(&revision::resolve_tree(rev)?)?,
+        None if staged => match file_altering::get_head_commit()? {
+            Some(commit) => file_altering::build_index_from_tree(
+                &file_altering::get_tree_from_commit(&commit)?,
+            )?,
+            None => index::Index::new(),
+        },
+        None => index::Index::load()?,
+    };
+    let selected = |path: &Path| pathspecs.iter().any(|spec| matches(spec, path));
+
+    for spec in pathspecs {
+        let known = |obj: &index::ObjectInfo| matches(spec, &obj.path);
+        if !source.obj.iter().any(known) && !current.obj.iter().any(known) {
+            return Err(anyhow!(
+                "Pathspec '{}' did not match any file known to rvcs.",
+                spec
+            ));
+        }
+    }
+    let unmerged: Vec<_> = current
+        .unmerged_paths()
+        .into_iter()
+        .filter(|path| selected(path))
+        .collect();
+    if from_index && !unmerged.is_empty() {
+        return Err(anyhow!(
+            "'{}' is unmerged, resolve it or restore it from a commit with --source.",
+            unmerged[0].display()
+        ));
+    }
+
+    if worktree {
+        for obj in current.obj.iter().filter(|obj| selected(&obj.path)) {
+            if !source.obj.iter().any(|entry| entry.path == obj.path) {
+                file_altering::remove_working_file(&obj.path)?;
+            }
+        }
+        for obj in source.obj.iter().filter(|obj| selected(&obj.path)) {
+            if obj.stage != 0 {
+                continue;
+            }
+            let content = store::read_object_of(&obj.hash, ObjectKind::Blob)?;
+            file_altering::write_working_file(&obj.path, &content, obj.permissions)?;
+        }
+    }
+    if staged {
+        current.obj.retain(|obj| !selected(&obj.path));
+        for obj in source.obj {
+            if selected(&obj.path) {
+                current.add_object(obj);
+            }
+        }
+        current.save_index_file_truncate()?;
+    }
+    Ok(())
+}
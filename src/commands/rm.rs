@@ -0,0 +1,86 @@
+use crate::commands::checkout;
+use crate::objects::store::{self, ObjectKind};
+use crate::others::{file_altering, index};
+use anyhow::anyhow;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The blobs of HEAD by path, empty before the first commit.
+pub fn head_files() -> Result<HashMap<String, String>> {
+    match file_altering::get_head_commit()? {
+        Some(commit) => {
+            checkout::get_files_from_tree(&file_altering::get_tree_from_commit(&commit)?)
+        }
+        None => Ok(HashMap::new()),
+    }
+}
+
+// The index entries a path names, every entry under it for a directory.
+pub fn tracked_under<'a>(current: &'a index::Index, path: &Path) -> Vec<&'a index::ObjectInfo> {
+    current
+        .obj
+        .iter()
+        .filter(|obj| obj.path.starts_with(path))
+        .collect()
+}
+
+/*
+ * Stops tracking the paths and deletes them from the working tree, or only
+ * from the index with --cached. Everything is checked before anything is
+ * removed: without --force a file whose content would be lost is refused, that
+ * is a staged version that is not in HEAD, or with a working tree deletion a
+ * working file that differs from the index.
+ * */
+pub fn rm_command(paths: &[String], cached: bool, force: bool) -> Result<()> {
+    let mut current = index::Index::load()?;
+    let head = head_files()?;
+    let mut removed: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let path = index::normalize_path(Path::new(path));
+        let entries = tracked_under(&current, &path);
+        if entries.is_empty() {
+            return Err(anyhow!(
+                "'{}' did not match any file known to rvcs.",
+                path.display()
+            ));
+        }
+        for obj in entries {
+            if removed.contains(&obj.path) {
+                continue;
+            }
+            let working = match fs::read(&obj.path) {
+                Ok(content) => Some(store::hash_object(ObjectKind::Blob, &content)),
+                Err(_) => None,
+            };
+            let name = obj.path.to_str().unwrap();
+            let staged_only = head.get(name) != Some(&obj.hash);
+            let working_only = working.as_ref().is_some_and(|hash| hash != &obj.hash);
+            let lost = if cached {
+                staged_only && working_only
+            } else {
+                staged_only || working_only
+            };
+            if lost && !force && obj.stage == 0 {
+                return Err(anyhow!(
+                    "'{}' has changes that would be lost, use --cached to keep the file or --force to remove it anyway.",
+                    name
+                ));
+            }
+            removed.push(obj.path.clone());
+        }
+    }
+
+    if !cached {
+        for path in &removed {
+            file_altering::remove_working_file(path)?;
+        }
+    }
+    current.obj.retain(|obj| !removed.contains(&obj.path));
+    current.save_index_file_truncate()?;
+    for path in removed {
+        println!("rm '{}'", path.display());
+    }
+    Ok(())
+}
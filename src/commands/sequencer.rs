@@ -0,0 +1,364 @@
+use crate::commands::checkout::{self, CheckoutMode};
+use crate::commands::merge;
+use crate::objects::commit::{Commit, Signature, SignatureOverrides};
+use crate::objects::tree::Tree;
+use crate::others::{file_altering, index, revision};
+use anyhow::anyhow;
+use anyhow::Result;
+use colored::*;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/*
+ * cherry-pick and revert apply commits one after the other onto HEAD. Picking
+ * a commit merges the changes from its parent to it, reverting one merges the
+ * changes from it back to its parent, HEAD being the other side either way.
+ * The state of the sequence is kept in .vcs/sequencer until the last commit is
+ * applied, so that it can be continued or aborted wherever it stops:
+ *     todo     "pick <hash>" or "revert <hash>" lines, the next commit first
+ *     head     HEAD before the sequence started, restored by --abort
+ *     message  the message of the commit that stopped on conflicts
+ * */
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Pick,
+    Revert,
+}
+
+impl Action {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Action::Pick => "pick",
+            Action::Revert => "revert",
+        }
+    }
+    fn command(&self) -> &'static str {
+        match self {
+            Action::Pick => "cherry-pick",
+            Action::Revert => "revert",
+        }
+    }
+    fn parse(name: &str) -> Result<Self> {
+        match name {
+            "pick" => Ok(Action::Pick),
+            "revert" => Ok(Action::Revert),
+            _ => Err(anyhow!("Unknown sequencer action '{}'", name)),
+        }
+    }
+}
+
+fn sequencer_dir() -> PathBuf {
+    Path::new(".vcs").join("sequencer")
+}
+
+pub fn in_progress() -> bool {
+    sequencer_dir().is_dir()
+}
+
+// Records what is left to do, a message from an earlier stop no longer applies.
+fn write_state(todo: &[(Action, String)], head: &str) -> Result<()> {
+    fs::create_dir_all(sequencer_dir())?;
+    let todo: String = todo
+        .iter()
+        .map(|(action, hash)| format!("{} {}\n", action.as_str(), hash))
+        .collect();
+    fs::write(sequencer_dir().join("todo"), todo)?;
+    fs::write(sequencer_dir().join("head"), head)?;
+    let message = sequencer_dir().join("message");
+    if message.exists() {
+        fs::remove_file(message)?;
+    }
+    Ok(())
+}
+
+fn read_todo() -> Result<Vec<(Action, String)>> {
+    let content = fs::read_to_string(sequencer_dir().join("todo"))?;
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (action, hash) = line
+                .split_once(' ')
+                .ok_or_else(|| anyhow!("Malformed sequencer line: {}", line))?;
+            Ok((Action::parse(action)?, hash.trim().to_string()))
+        })
+        .collect()
+}
+
+fn read_state(name: &str) -> Result<String> {
+    Ok(fs::read_to_string(sequencer_dir().join(name))?
+        .trim()
+        .to_string())
+}
+
+// The tree a commit's changes are taken from, an empty one for a root commit.
+fn parent_tree(commit: &Commit) -> Result<String> {
+    match commit.content.parents.as_slice() {
+        [] => {
+            let empty = Tree::new_tree_from_index(index::Index::new())?;
+            empty.create_tree()?;
+            Ok(empty.id)
+        }
+        [parent] => file_altering::get_tree_from_commit(parent),
+        _ => Err(anyhow!(
+            "Commit {} is a merge, it cannot be picked or reverted.",
+            &commit.id[..7]
+        )),
+    }
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or("")
+}
+
+// Picked commits keep their author, reverts are authored by whoever reverts.
+fn new_commit(tree: &str, action: Action, original: &Commit, message: &str) -> Result<()> {
+    let head = revision::resolve_commit("HEAD")?;
+    let (author, committer) = Signature::for_commit(&SignatureOverrides::default())?;
+    let author = match action {
+        Action::Pick => original.content.author.clone(),
+        Action::Revert => author,
+    };
+    let commit = Commit::new(
+        tree.to_string(),
+        vec![head],
+        author,
+        committer,
+        message.to_string(),
+    );
+    commit.create_commit()?;
+    println!("Commit created successfully with ID: {}", commit.id);
+    file_altering::update_head(
+        &commit.id,
+        &format!("{}: {}", action.command(), subject(message)),
+    )
+}
+
+// The base and the other side of the merge that applies a commit, with the message of the result.
+fn sides(action: Action, original: &Commit) -> Result<(String, String, String)> {
+    let hash = &original.id;
+    Ok(match action {
+        Action::Pick => (
+            parent_tree(original)?,
+            original.content.tree.clone(),
+            format!(
+                "{}\n\n(cherry picked from commit {})",
+                original.content.message, hash
+            ),
+        ),
+        Action::Revert => (
+            original.content.tree.clone(),
+            parent_tree(original)?,
+            format!(
+                "Revert \"{}\"\n\nThis reverts commit {}.",
+                subject(&original.content.message),
+                hash
+            ),
+        ),
+    })
+}
+
+fn read_commit(hash: &str) -> Result<Commit> {
+    Ok(Commit {
+        id: hash.to_string(),
+        content: Commit::read(hash)?,
+    })
+}
+
+/*
+ * Applies one commit onto HEAD and commits the result. Returns false when it
+ * stopped on conflicts, which are left in the working tree and the index.
+ * */
+fn apply(action: Action, hash: &str) -> Result<(bool, String)> {
+    let original = read_commit(hash)?;
+    let short = format!("{} {}", &hash[..7], subject(&original.content.message));
+    let (base, theirs, message) = sides(action, &original)?;
+    let head_tree = file_altering::get_current_tree()?;
+    let merged = merge::merge_trees(&base, &head_tree, &theirs, ("HEAD", &short), false)?;
+    checkout::ensure_unchanged(&head_tree, &merge::touched_paths(&head_tree, &merged)?)?;
+    if !merged.conflicts.is_empty() {
+        merge::write_merge_result(&head_tree, &merged)?;
+        for (path, kind) in &merged.conflicts {
+            println!(
+                "{} ({}): Merge conflict in {}",
+                "CONFLICT".red().bold(),
+                kind,
+                path
+            );
+        }
+        return Ok((false, message));
+    }
+
+    let merged_tree = Tree::new_tree_from_index(merged.index)?;
+    merged_tree.create_tree()?;
+    if merged_tree.id == head_tree {
+        println!("{} {} changes nothing, skipped.", action.command(), short);
+        return Ok((true, message));
+    }
+    checkout::switch_trees(
+        Some(&head_tree),
+        &merged_tree.id,
+        CheckoutMode::Safe,
+        &short,
+    )?;
+    new_commit(&merged_tree.id, action, &original, &message)?;
+    Ok((true, message))
+}
+
+/*
+ * Works through the todo list, saving what is left before every commit. The
+ * state goes away once everything is applied and stays when a commit fails or
+ * conflicts, the message of a conflicted commit is kept for --continue.
+ * */
+fn run(todo: Vec<(Action, String)>, orig_head: &str) -> Result<()> {
+    for (i, (action, hash)) in todo.iter().enumerate() {
+        write_state(&todo[i..], orig_head)?;
+        let (done, message) = apply(*action, hash).map_err(|err| {
+            anyhow!(
+                "{}\nCould not {} {}, fix this and run `rvcs {} --continue`, or `rvcs {} --abort`.",
+                err,
+                action.as_str(),
+                &hash[..7],
+                action.command(),
+                action.command()
+            )
+        })?;
+        if !done {
+            fs::write(sequencer_dir().join("message"), message)?;
+            return Err(anyhow!(
+                "Could not {} {}, fix the conflicts, `rvcs add` the files and run `rvcs {} --continue`, or `rvcs {} --abort`.",
+                action.as_str(),
+                &hash[..7],
+                action.command(),
+                action.command()
+            ));
+        }
+    }
+    finish()
+}
+
+// Drops the state, and ORIG_HEAD with it, which would keep the replaced commits from gc.
+fn finish() -> Result<()> {
+    fs::remove_dir_all(sequencer_dir())?;
+    let orig_head = Path::new(".vcs").join("ORIG_HEAD");
+    if orig_head.exists() {
+        fs::remove_file(orig_head)?;
+    }
+    Ok(())
+}
+
+// Paths whose content differs between two trees.
+fn changed_paths(from_tree: &str, to_tree: &str) -> Result<BTreeSet<PathBuf>> {
+    let from = checkout::get_files_from_tree(from_tree)?;
+    let to = checkout::get_files_from_tree(to_tree)?;
+    Ok(from
+        .keys()
+        .chain(to.keys())
+        .filter(|path| from.get(*path) != to.get(*path))
+        .map(PathBuf::from)
+        .collect())
+}
+
+pub fn sequence_command(action: Action, revs: &[String]) -> Result<()> {
+    if in_progress() {
+        return Err(anyhow!(
+            "A cherry-pick or revert is in progress, use --continue or --abort first."
+        ));
+    }
+    if merge::merge_head()?.is_some() {
+        return Err(anyhow!("A merge is in progress, finish or abort it first."));
+    }
+    if revs.is_empty() {
+        return Err(anyhow!("A commit to {} is required.", action.as_str()));
+    }
+    let orig_head = file_altering::get_head_commit()?
+        .ok_or_else(|| anyhow!("The current branch has no commits yet."))?;
+    let staged = Tree::new_tree_from_index(index::Index::load()?)?;
+    if staged.id != file_altering::get_tree_from_commit(&orig_head)? {
+        return Err(anyhow!(
+            "Your index has staged changes, commit or stash them first."
+        ));
+    }
+    let todo = revs
+        .iter()
+        .map(|rev| Ok((action, revision::resolve_commit(rev)?)))
+        .collect::<Result<Vec<_>>>()?;
+    run(todo, &orig_head)
+}
+
+/*
+ * Commits the resolved conflicts of the stopped commit and goes on with the
+ * rest. A commit that failed without conflicts is tried again.
+ * */
+pub fn sequence_continue() -> Result<()> {
+    if !in_progress() {
+        return Err(anyhow!("There is no cherry-pick or revert in progress."));
+    }
+    let index = index::Index::load()?;
+    let unmerged = index.unmerged_paths();
+    if !unmerged.is_empty() {
+        let mut output = String::from("Fix the conflicts and `rvcs add` the files first:");
+        for path in unmerged {
+            output.push_str(&format!("\n\t{}", path.display()));
+        }
+        return Err(anyhow!(output));
+    }
+    let todo = read_todo()?;
+    let orig_head = read_state("head")?;
+    if !sequencer_dir().join("message").exists() {
+        return run(todo, &orig_head);
+    }
+    let message = read_state("message")?;
+    let Some(((action, hash), rest)) = todo.split_first() else {
+        return Err(anyhow!("The sequencer has nothing left to do."));
+    };
+    let tree = Tree::new_tree_from_index(index)?;
+    tree.create_tree()?;
+    if tree.id == file_altering::get_current_tree()? {
+        println!(
+            "{} {} changes nothing, skipped.",
+            action.command(),
+            &hash[..7]
+        );
+    } else {
+        let original = read_commit(hash)?;
+        new_commit(&tree.id, *action, &original, &message)?;
+    }
+    run(rest.to_vec(), &orig_head)
+}
+
+/*
+ * Throws the whole sequence away, HEAD goes back to where it started. Only the
+ * paths the sequence wrote are restored: the ones the committed picks changed,
+ * the ones the stopped commit touched and the unmerged ones. Other local
+ * changes stay as they are.
+ * */
+pub fn sequence_abort() -> Result<()> {
+    if !in_progress() {
+        return Err(anyhow!("There is no cherry-pick or revert in progress."));
+    }
+    let orig_head = read_state("head")?;
+    let orig_tree = file_altering::get_tree_from_commit(&orig_head)?;
+    let head_tree = file_altering::get_current_tree()?;
+    // without conflicts the working tree only has the committed picks and local changes
+    if !sequencer_dir().join("message").exists() {
+        checkout::switch_trees(Some(&head_tree), &orig_tree, CheckoutMode::Safe, &orig_head)?;
+    } else {
+        let mut paths = changed_paths(&head_tree, &orig_tree)?;
+        // the stopped commit is merged again to know which paths it wrote
+        if let Some((action, hash)) = read_todo()?.first() {
+            let (base, theirs, _) = sides(*action, &read_commit(hash)?)?;
+            let merged = merge::merge_trees(&base, &head_tree, &theirs, ("HEAD", hash), false)?;
+            paths.extend(
+                merge::touched_paths(&head_tree, &merged)?
+                    .into_iter()
+                    .map(PathBuf::from),
+            );
+        }
+        paths.extend(index::Index::load()?.unmerged_paths());
+        merge::restore_paths(&orig_tree, &paths)?;
+    }
+    file_altering::update_head(&orig_head, &format!("reset: moving to {}", orig_head))?;
+    finish()
+}
@@ -0,0 +1,234 @@
+use crate::commands::checkout::{self, CheckoutMode};
+use crate::commands::diff::{self, DiffOptions};
+use crate::commands::{merge, reset};
+use crate::objects::commit::{Commit, Signature, SignatureOverrides};
+use crate::objects::store::{self, ObjectKind};
+use crate::objects::tree::Tree;
+use crate::others::{file_altering, index, reflog, refs};
+use anyhow::anyhow;
+use anyhow::Result;
+use colored::*;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+/*
+ * A stash is a commit like git's: its tree is the tracked files as they were in
+ * the working tree, its first parent is HEAD at the time and its second parent
+ * a commit of the index. refs/stash points at the latest one and its log keeps
+ * the others, stash@{n} is the n-th most recent.
+ * */
+const STASH_REF: &str = "refs/stash";
+
+// "stash@{n}", or just n, the latest stash by default.
+fn stash_number(selector: Option<&str>) -> Result<usize> {
+    let Some(selector) = selector else {
+        return Ok(0);
+    };
+    let number = selector
+        .strip_prefix("stash@{")
+        .and_then(|rest| rest.strip_suffix('}'))
+        .unwrap_or(selector);
+    number
+        .parse()
+        .map_err(|_| anyhow!("'{}' is not a stash, expected stash@{{n}}", selector))
+}
+
+fn stash_commit(n: usize) -> Result<String> {
+    let entries = reflog::read(STASH_REF)?;
+    entries
+        .len()
+        .checked_sub(n + 1)
+        .map(|i| entries[i].new.clone())
+        .ok_or_else(|| anyhow!("stash@{{{}}} does not exist", n))
+}
+
+fn create_commit(tree: &str, parents: Vec<String>, message: &str) -> Result<String> {
+    let (author, committer) = Signature::for_commit(&SignatureOverrides::default())?;
+    let commit = Commit::new(
+        tree.to_string(),
+        parents,
+        author,
+        committer,
+        message.to_string(),
+    );
+    commit.create_commit()?;
+    Ok(commit.id)
+}
+
+// The tree of the tracked files with their working tree content, deleted ones left out.
+fn working_tree(current: &index::Index) -> Result<String> {
+    let mut working = index::Index::new();
+    for obj in &current.obj {
+        let Ok(content) = fs::read(&obj.path) else {
+            continue;
+        };
+        let hash = store::write_object(ObjectKind::Blob, &content)?;
+        let mode = fs::metadata(&obj.path)?.permissions().mode();
+        working.add_object(index::ObjectInfo::from_tree_entry(&obj.path, &hash, mode));
+    }
+    let tree = Tree::new_tree_from_index(working)?;
+    tree.create_tree()?;
+    Ok(tree.id)
+}
+
+/*
+ * Saves the staged and unstaged changes to tracked files as a new stash, then
+ * brings the working tree and the index back to HEAD. Untracked files stay.
+ * */
+pub fn stash_push(message: Option<&str>) -> Result<()> {
+    let head = file_altering::get_head_commit()?.ok_or_else(|| {
+        anyhow!("The current branch has no commits yet, there is nothing to stash.")
+    })?;
+    if merge::merge_head()?.is_some() {
+        return Err(anyhow!("A merge is in progress, finish or abort it first."));
+    }
+    let current = index::Index::load()?;
+    if !current.unmerged_paths().is_empty() {
+        return Err(anyhow!("Resolve the conflicts in the index first."));
+    }
+    let head_tree = file_altering::get_tree_from_commit(&head)?;
+    let index_tree = Tree::new_tree_from_index(index::Index::load()?)?;
+    index_tree.create_tree()?;
+    let work_tree = working_tree(&current)?;
+    if index_tree.id == head_tree && work_tree == head_tree {
+        println!("No local changes to save");
+        return Ok(());
+    }
+
+    let branch = file_altering::get_head_branch()?.unwrap_or_else(|| String::from("(no branch)"));
+    let subject = Commit::read(&head)?.message;
+    let subject = subject.lines().next().unwrap_or("");
+    let description = match message {
+        Some(message) => format!("On {}: {}", branch, message),
+        None => format!("WIP on {}: {} {}", branch, &head[..7], subject),
+    };
+    let index_commit = create_commit(
+        &index_tree.id,
+        vec![head.clone()],
+        &format!("index on {}: {} {}", branch, &head[..7], subject),
+    )?;
+    let stash = create_commit(&work_tree, vec![head.clone(), index_commit], &description)?;
+    refs::update_ref(STASH_REF, &stash, &description)?;
+
+    // files added since HEAD are in the stash now, they leave the working tree
+    let head_files = checkout::get_files_from_tree(&head_tree)?;
+    for obj in &current.obj {
+        if !head_files.contains_key(obj.path.to_str().unwrap()) {
+            file_altering::remove_working_file(&obj.path)?;
+        }
+    }
+    checkout::switch_trees(Some(&head_tree), &head_tree, CheckoutMode::Force, "HEAD")?;
+    println!("Saved working directory and index state {}", description);
+    Ok(())
+}
+
+pub fn stash_list() -> Result<()> {
+    for (n, entry) in reflog::read(STASH_REF)?.iter().rev().enumerate() {
+        println!("{}: {}", format!("stash@{{{}}}", n).yellow(), entry.reason);
+    }
+    Ok(())
+}
+
+// The changes a stash records, against the commit it was made on.
+pub fn stash_show(selector: Option<&str>) -> Result<()> {
+    let stash = stash_commit(stash_number(selector)?)?;
+    let parent = Commit::read(&stash)?
+        .parents
+        .first()
+        .cloned()
+        .ok_or_else(|| anyhow!("stash {} has no parent", &stash[..7]))?;
+    println!(
+        "{}",
+        diff::diff_between_commits(&parent, &stash, &DiffOptions::default())?
+    );
+    Ok(())
+}
+
+/*
+ * Merges the changes of a stash into the working tree: the commit it was made
+ * on is the base, HEAD and the stash are the two sides. Files new in the stash
+ * are staged, other changes are left unstaged. Returns false when some files
+ * were left with conflicts.
+ * */
+pub fn stash_apply(selector: Option<&str>) -> Result<bool> {
+    let stash = stash_commit(stash_number(selector)?)?;
+    let head = file_altering::get_head_commit()?
+        .ok_or_else(|| anyhow!("The current branch has no commits yet."))?;
+    if merge::merge_head()?.is_some() {
+        return Err(anyhow!("A merge is in progress, finish or abort it first."));
+    }
+    let base = Commit::read(&stash)?
+        .parents
+        .first()
+        .cloned()
+        .ok_or_else(|| anyhow!("stash {} has no parent", &stash[..7]))?;
+    let base_tree = file_altering::get_tree_from_commit(&base)?;
+    let head_tree = file_altering::get_tree_from_commit(&head)?;
+    let stash_tree = file_altering::get_tree_from_commit(&stash)?;
+
+    let merged = merge::merge_trees(
+        &base_tree,
+        &head_tree,
+        &stash_tree,
+        ("Updated upstream", "Stashed changes"),
+        false,
+    )?;
+    let touched = merge::touched_paths(&head_tree, &merged)?;
+    checkout::ensure_unchanged(&head_tree, &touched)?;
+    if !merged.conflicts.is_empty() {
+        merge::write_merge_result(&head_tree, &merged)?;
+        for (path, kind) in &merged.conflicts {
+            println!(
+                "{} ({}): Merge conflict in {}",
+                "CONFLICT".red().bold(),
+                kind,
+                path
+            );
+        }
+        println!("The stash is kept, resolve the conflicts and `rvcs add` the files.");
+        return Ok(false);
+    }
+
+    let merged_tree = Tree::new_tree_from_index(merged.index)?;
+    merged_tree.create_tree()?;
+    checkout::switch_trees(
+        Some(&head_tree),
+        &merged_tree.id,
+        CheckoutMode::Safe,
+        "stash",
+    )?;
+    // the changes to files HEAD has stay unstaged
+    let head_files = checkout::get_files_from_tree(&head_tree)?;
+    let unstage: Vec<String> = touched
+        .into_iter()
+        .filter(|path| head_files.contains_key(path))
+        .collect();
+    if !unstage.is_empty() {
+        reset::reset_paths(None, &unstage)?;
+    }
+    Ok(true)
+}
+
+// Forgets a stash, refs/stash moves to the next one or goes away with the last.
+pub fn stash_drop(selector: Option<&str>) -> Result<()> {
+    let n = stash_number(selector)?;
+    let stash = stash_commit(n)?;
+    reflog::drop_entry(STASH_REF, n)?;
+    match reflog::read(STASH_REF)?.last() {
+        Some(latest) => refs::write_ref(STASH_REF, &latest.new)?,
+        None => {
+            refs::delete_ref(STASH_REF)?;
+            reflog::delete(STASH_REF)?;
+        }
+    }
+    println!("Dropped stash@{{{}}} ({})", n, &stash[..7]);
+    Ok(())
+}
+
+// Applies a stash and drops it, unless the changes conflicted.
+pub fn stash_pop(selector: Option<&str>) -> Result<()> {
+    if stash_apply(selector)? {
+        stash_drop(selector)?;
+    }
+    Ok(())
+}
@@ -1,10 +1,13 @@
-use crate::commands::dec_object::dec_obj;
-use crate::objects::commit;
+use crate::commands::{merge, sequencer};
+use crate::objects::commit::Commit;
 use crate::others::file_altering;
+use crate::others::index::Index;
 use anyhow::Result;
+use colored::*;
 use glob::Pattern;
+use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub fn parse_gitignore() -> Result<Vec<Pattern>, anyhow::Error> {
     let gitignore_path = ".ignore";
@@ -31,16 +34,94 @@ pub fn parse_gitignore() -> Result<Vec<Pattern>, anyhow::Error> {
     Ok(patterns)
 }
 
+// Which side kept a conflicted file, from the stages the merge recorded for it.
+fn unmerged_kind(stages: &[u8]) -> &'static str {
+    match (
+        stages.contains(&1),
+        stages.contains(&2),
+        stages.contains(&3),
+    ) {
+        (false, true, true) => "both added",
+        (_, true, false) => "deleted by them",
+        (_, false, true) => "deleted by us",
+        _ => "both modified",
+    }
+}
+
+fn print_merge_state(
+    index: &Index,
+    unmerged_paths: &[PathBuf],
+    content: &mut String,
+) -> Result<()> {
+    let merging = merge::merge_head()?.is_some();
+    if !merging && unmerged_paths.is_empty() {
+        return Ok(());
+    }
+    if unmerged_paths.is_empty() {
+        let line = "All conflicts fixed but you are still merging (use \"rvcs merge --continue\")";
+        println!("{}", line);
+        content.push_str(line);
+        content.push('\n');
+        return Ok(());
+    }
+    // a stash that did not apply cleanly leaves conflicts without a merge
+    if sequencer::in_progress() {
+        println!("You have unmerged paths (fix them and run \"rvcs add\", then \"rvcs cherry-pick --continue\" or \"rvcs revert --continue\")");
+    } else if merging {
+        println!(
+            "You have unmerged paths (fix them and run \"rvcs add\", or \"rvcs merge --abort\")"
+        );
+    } else {
+        println!("You have unmerged paths (fix them and run \"rvcs add\")");
+    }
+    println!("Unmerged paths:");
+    for path in unmerged_paths {
+        let stages: Vec<u8> = index
+            .obj
+            .iter()
+            .filter(|obj| &obj.path == path)
+            .map(|obj| obj.stage)
+            .collect();
+        let line = format!("\t{}: {}", unmerged_kind(&stages), path.display());
+        println!("{}", line);
+        content.push_str(&line);
+        content.push('\n');
+    }
+    Ok(())
+}
+
+fn print_changes(title: &str, changes: &[(&str, String)], content: &mut String) {
+    if changes.is_empty() {
+        return;
+    }
+    println!("{}", title);
+    content.push_str(title);
+    content.push('\n');
+    for (kind, path) in changes {
+        let line = format!("\t{}: {}", kind, path);
+        println!("{}", line);
+        content.push_str(&line);
+        content.push('\n');
+    }
+}
+
 pub fn status_command() -> Result<String> {
     let current_branch = file_altering::get_curent_branch()?.trim().to_string();
     let mut content = String::new();
-    println!("On branch '{}'", current_branch);
-    content.push_str(&format!("On branch '{}'", current_branch));
-    content.push('\n');
     // Get the current commit and tree
-    let mut current_branch_path = String::from(".vcs/");
-    current_branch_path.push_str(&file_altering::get_curent_branch()?);
-    let current_commit_hash = fs::read_to_string(&current_branch_path)?.trim().to_string();
+    let current_commit_hash = file_altering::get_head_commit()?.unwrap_or_default();
+    let head_line = if current_branch.starts_with("refs/") {
+        format!("On branch '{}'", current_branch)
+    } else {
+        format!(
+            "{} HEAD detached at {}, new commits will not be on any branch",
+            "Warning:".yellow().bold(),
+            &current_commit_hash[..7]
+        )
+    };
+    println!("{}", head_line);
+    content.push_str(&head_line);
+    content.push('\n');
 
     let working_files = file_altering::get_working_files()?;
     let mut untracked_files = Vec::new();
@@ -58,104 +139,94 @@ pub fn status_command() -> Result<String> {
         }
         return Ok(String::new());
     }
-    let current_pretty_commit = dec_obj(&current_commit_hash)?;
-    let current_commit_content_obj =
-        commit::CommitContent::from_pretty_print(&current_pretty_commit)?;
+    let current_commit_content_obj = Commit::read(&current_commit_hash)?;
     let current_tree = current_commit_content_obj.tree;
-    let (index_files, deleted_files) = file_altering::get_files_from_tree(&current_tree)?;
+    let head_files: HashMap<String, String> = file_altering::build_index_from_tree(&current_tree)?
+        .obj
+        .into_iter()
+        .map(|obj| (obj.path.to_str().unwrap().to_string(), obj.hash))
+        .collect();
 
     // get current index files
-    // creating a new tree from the actual index
-    let new_tree = crate::objects::tree::Tree::new()?;
-    new_tree.create_tree()?;
-    let (mut new_index, deleted_fil) = file_altering::get_files_from_tree(&new_tree.id)?;
-    let mut modified_files = Vec::new();
-    let mut staged_files = Vec::new();
-    let mut added_files = Vec::new();
-
-    for working_file in &working_files.obj {
-        if let Some(index_file) = index_files.obj.iter().find(|index_file| {
-            let mut temp = String::from("./");
-            let temp1 = index_file.path.to_str().unwrap();
-            temp.push_str(temp1);
-            temp == working_file.path.to_str().unwrap()
-        }) {
-            if index_file.hash != working_file.hash {
-                modified_files.push(working_file.path.to_str().unwrap().to_string());
-            } else {
-                staged_files.push(working_file.path.to_str().unwrap().to_string());
-            }
-        } else {
-            untracked_files.push(working_file.path.to_str().unwrap().to_string());
+    let mut new_index = crate::others::index::Index::load()?;
+    let unmerged_paths = new_index.unmerged_paths();
+    print_merge_state(&new_index, &unmerged_paths, &mut content)?;
+    let unmerged: Vec<&str> = unmerged_paths
+        .iter()
+        .map(|path| path.to_str().unwrap())
+        .collect();
+    let staged: HashMap<String, String> = new_index
+        .obj
+        .iter()
+        .filter(|obj| obj.stage == 0)
+        .map(|obj| (obj.path.to_str().unwrap().to_string(), obj.hash.clone()))
+        .collect();
+    let deleted_fil: Vec<String> = new_index
+        .obj
+        .iter()
+        .filter(|obj| obj.stage == 0 && !obj.path.exists())
+        .map(|obj| obj.path.to_str().unwrap().to_string())
+        .collect();
+
+    // the index against HEAD, a move shows as a deletion and an addition
+    let mut to_commit: Vec<(&str, String)> = Vec::new();
+    for (path, hash) in &staged {
+        match head_files.get(path) {
+            None => to_commit.push(("added", path.clone())),
+            Some(head_hash) if head_hash != hash => to_commit.push(("modified", path.clone())),
+            Some(_) => {}
+        }
+    }
+    for path in head_files.keys() {
+        if !staged.contains_key(path) && !unmerged.contains(&path.as_str()) {
+            to_commit.push(("deleted", path.clone()));
         }
     }
+    to_commit.sort_by(|a, b| a.1.cmp(&b.1));
 
-    for file in &new_index.obj {
-        if let Some(_index_file) = index_files
-            .obj
-            .iter()
-            .find(|index_file| index_file.path == file.path)
-        {
-        } else {
-            added_files.push(file.path.to_str().unwrap().to_string());
-            untracked_files.retain(|ff| {
-                let mut temp = String::from("./");
-                let temp1 = file.path.to_str().unwrap();
-                temp.push_str(temp1);
-                temp != *ff
-            });
+    // the working tree against the index
+    let mut not_staged: Vec<(&str, String)> = Vec::new();
+    let mut untracked_files = Vec::new();
+    for working_file in &working_files.obj {
+        let path = working_file
+            .path
+            .strip_prefix("./")
+            .unwrap_or(&working_file.path);
+        let path = path.to_str().unwrap().to_string();
+        // conflicted files are already listed as unmerged
+        if unmerged.contains(&path.as_str()) {
+            continue;
+        }
+        match staged.get(&path) {
+            Some(hash) if *hash != working_file.hash => not_staged.push(("modified", path)),
+            Some(_) => {}
+            None => untracked_files.push(path),
         }
     }
+    for path in &deleted_fil {
+        not_staged.push(("deleted", path.clone()));
+    }
+    not_staged.sort_by(|a, b| a.1.cmp(&b.1));
 
-    if modified_files.is_empty()
+    // unmerged paths are pending changes too, they were listed above
+    if unmerged_paths.is_empty()
+        && to_commit.is_empty()
+        && not_staged.is_empty()
         && untracked_files.is_empty()
-        && deleted_files.is_empty()
-        && added_files.is_empty()
     {
         let ttt = String::from("nothing to commit, working tree clean");
         println!("{}", ttt);
         content.push_str(&ttt);
         content.push('\n');
     } else {
-        if !modified_files.is_empty() {
-            let ttt = String::from("Changes not staged for commit:");
-            println!("{}", ttt);
-            content.push_str(&ttt);
-            content.push('\n');
-            for file in modified_files {
-                println!("\tmodified: {}", file);
-
-                content.push_str(&format!("\tmodified: {}", file));
-                content.push('\n');
-            }
-        }
-
-        if !deleted_files.is_empty() {
-            println!("Changes not staged for commit:");
-            for file in deleted_files {
-                println!("\tdeleted: {}", file);
-
-                content.push_str(&format!("\tdeleted {}", file));
-                content.push('\n');
-            }
-        }
-
+        print_changes("Changes to be committed:", &to_commit, &mut content);
+        print_changes("Changes not staged for commit:", &not_staged, &mut content);
         if !untracked_files.is_empty() {
             println!("Untracked files:");
             for file in untracked_files {
                 println!("\t{}", file);
             }
         }
-
-        if !added_files.is_empty() {
-            println!("Added files:");
-            for file in added_files {
-                println!("\tadded {}", file);
-
-                content.push_str(&format!("\tadded: {}", file));
-                content.push('\n');
-            }
-        }
     }
 
     // need to update index in case a file is deleted after it is added
@@ -0,0 +1,84 @@
+use crate::objects::commit::{Commit, Signature, SignatureOverrides};
+use crate::objects::store::{self, ObjectKind};
+use crate::objects::tag::Tag;
+use crate::others::{refs, revision};
+use anyhow::anyhow;
+use anyhow::Result;
+
+fn tag_ref(name: &str) -> String {
+    format!("refs/tags/{}", name)
+}
+
+/*
+ * Tags the revision, HEAD by default. Without a message the tag is lightweight,
+ * a ref pointing straight at the object. With one, a tag object recording the
+ * tagger, the date and the message is created and the ref points at it.
+ * */
+pub fn tag_command(
+    name: &str,
+    target: Option<&str>,
+    message: Option<&str>,
+    force: bool,
+    overrides: &SignatureOverrides,
+) -> Result<()> {
+    refs::check_ref_name(name)?;
+    if !force && refs::read_ref(&tag_ref(name))?.is_some() {
+        return Err(anyhow!("Tag '{}' already exists, use -f to move it.", name));
+    }
+    if let Some(existing) = refs::clashing_ref(&tag_ref(name))? {
+        return Err(anyhow!(
+            "Cannot create tag '{}', it clashes with the existing ref '{}'.",
+            name,
+            existing
+        ));
+    }
+    let object = revision::resolve_object(target.unwrap_or("HEAD"))?;
+    let hash = match message {
+        Some(message) => {
+            let message = message.trim();
+            if message.is_empty() {
+                return Err(anyhow!("An annotated tag needs a message."));
+            }
+            let kind = store::read_object(&object)?.0;
+            let tagger = Signature::for_tag(overrides)?;
+            let tag = Tag::new(object, kind, name.to_string(), tagger, message.to_string());
+            tag.create_tag()?;
+            tag.id
+        }
+        None => object,
+    };
+    refs::write_ref(&tag_ref(name), &hash)?;
+    Ok(())
+}
+
+// First line of the tag message, or of the commit message for lightweight tags.
+fn summary(hash: &str) -> Result<String> {
+    let message = match store::read_object(hash)?.0 {
+        ObjectKind::Tag => Tag::read(hash)?.message,
+        ObjectKind::Commit => Commit::read(hash)?.message,
+        _ => String::new(),
+    };
+    Ok(message.lines().next().unwrap_or("").to_string())
+}
+
+pub fn list_tags(show_messages: bool) -> Result<()> {
+    for (name, hash) in refs::list_refs()? {
+        let Some(tag) = name.strip_prefix("refs/tags/") else {
+            continue;
+        };
+        if show_messages {
+            println!("{:<15} {}", tag, summary(&hash)?);
+        } else {
+            println!("{}", tag);
+        }
+    }
+    Ok(())
+}
+
+pub fn delete_tag(name: &str) -> Result<()> {
+    let hash =
+        refs::read_ref(&tag_ref(name))?.ok_or_else(|| anyhow!("Tag '{}' does not exist.", name))?;
+    refs::delete_ref(&tag_ref(name))?;
+    println!("Deleted tag '{}' (was {}).", name, &hash[..7]);
+    Ok(())
+}
@@ -1,4 +1,5 @@
-use clap::{arg, command, Command};
+use clap::{arg, command, ArgMatches, Command};
+use objects::commit::SignatureOverrides;
 mod commands;
 mod objects;
 mod others;
@@ -19,12 +20,33 @@ fn cli() -> Command {
         .subcommand(
             Command::new("commit")
                 .about("Record changes to the repository")
-                .arg(arg!([NAME] "The commit message").required(true)),
+                .arg(arg!([NAME] "The commit message").required(true))
+                .arg(arg!(--author <AUTHOR> "Override the author, given as 'Name <email>'").required(false))
+                .arg(arg!(--date <DATE> "Override the author and committer date, given as '<epoch> [+hhmm]'").required(false)),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Get and set repository options, such as user.name and user.email")
+                .arg(arg!([KEY] "The option, as <section>.<name>"))
+                .arg(arg!([VALUE] "The new value"))
+                .arg(arg!(-l --list "List all options").required(false)),
         )
         .subcommand(
             Command::new("dec-object")
                 .about("Decompressing an object information and the print in the standard output")
-                .arg(arg!([NAME]"The hash of the object").required(true)),
+                .arg(arg!([NAME]"The hash of the object, may be abbreviated, or a revision").required(true))
+                .arg(arg!(-t --type "Print the type of the object instead of its content").required(false)),
+        )
+        .subcommand(
+            Command::new("log")
+                .about("Show the commit history")
+                .arg(arg!([REV] "Where to start, HEAD by default, or a range such as main..dev"))
+                .arg(arg!(--oneline "Show each commit on a single line").required(false))
+                .arg(arg!(-n --"max-count" <N> "Show at most N commits").required(false).value_parser(clap::value_parser!(usize)))
+                .arg(arg!(--"first-parent" "Only follow the first parent of merge commits").required(false))
+                .arg(arg!(--"date-order" "Show commits by committer date, never a parent before its children (default)").required(false))
+                .arg(arg!(--"topo-order" "Keep the commits of each line of history together").required(false).conflicts_with("date-order"))
+                .arg(arg!(--graph "Draw the history as an ASCII graph").required(false)),
         )
         .subcommand(
             Command::new("ls-files")
@@ -37,37 +59,75 @@ fn cli() -> Command {
         .subcommand(
             Command::new("commit-tree")
                 .about("Create a commit object that reference a tree")
-                .arg(arg!([TREE_HASH]"Tree hash").required(true))
-                .arg(arg!([COMMIT_MSG]"The commit message").required(true)),
+                .arg(arg!([TREE_HASH]"Tree hash, or a revision whose tree is used").required(true))
+                .arg(arg!([COMMIT_MSG]"The commit message").required(true))
+                .arg(arg!(--author <AUTHOR> "Override the author, given as 'Name <email>'").required(false))
+                .arg(arg!(--date <DATE> "Override the author and committer date, given as '<epoch> [+hhmm]'").required(false)),
         )
         .subcommand(
             Command::new("branch")
-                .about("Create a new branch")
-                .arg(arg!([NAME]"Branch name").required(true)),
+                .about("List branches, or create, delete and rename one")
+                .arg(arg!([NAME]"Branch name, the branches are listed without it").required(false))
+                .arg(arg!([START_POINT]"Commit the new branch starts at, the new name with -m").required(false))
+                .arg(arg!(-d --delete "Delete a branch that is merged into HEAD").required(false).requires("NAME").conflicts_with_all(["START_POINT", "move"]))
+                .arg(arg!(force_delete: -D "Delete a branch even if it is not merged").required(false).requires("NAME").conflicts_with_all(["START_POINT", "move", "delete"]))
+                .arg(arg!(-m --move "Rename the current branch to NAME, or NAME to START_POINT").required(false).requires("NAME")),
         )
         .subcommand(
             Command::new("checkout")
-                .about("Moving to a a branch")
-                .arg(arg!([NAME]"Branch Name").required(true)),
+                .about("Moving to a a branch, or detaching HEAD at any other commit")
+                .arg(arg!([NAME]"Branch name or commit, the start point with -b").required_unless_present("new-branch"))
+                .arg(arg!(-b <NEW_BRANCH> "Create a branch at NAME, HEAD by default, and switch to it").id("new-branch").required(false))
+                .arg(arg!(-f --force "Throw away local changes to the files the checkout touches").required(false))
+                .arg(arg!(-m --merge "Merge local changes into the checked out version of the files").required(false).conflicts_with("force")),
+        )
+        .subcommand(
+            Command::new("tag")
+                .about("List tags, or create and delete one")
+                .arg(arg!([NAME]"Tag name, the tags are listed without it").required(false))
+                .arg(arg!([REV]"Revision to tag, HEAD by default").required(false))
+                .arg(arg!(-a --annotate "Create an annotated tag object, a message is required").required(false).requires("message"))
+                .arg(arg!(-m --message <MESSAGE> "Message of an annotated tag, implies -a").required(false))
+                .arg(arg!(-f --force "Move the tag if it already exists").required(false))
+                .arg(arg!(-d --delete "Delete the tag").required(false).requires("NAME").conflicts_with_all(["REV", "message", "force"]))
+                .arg(arg!(show_messages: -n "List the tags with the first line of their message").required(false))
+                .arg(arg!(--date <DATE> "Override the tagger date, given as '<epoch> [+hhmm]'").required(false)),
+        )
+        .subcommand(
+            Command::new("switch")
+                .about("Switch to a branch")
+                .arg(arg!([BRANCH]"Branch name, the start point with -c").required_unless_present("create"))
+                .arg(arg!(-c --create <NEW_BRANCH> "Create a branch at BRANCH, HEAD by default, and switch to it").required(false)),
         )
         .subcommand(
             Command::new("diff-files")
                 .about("Diff between files")
                 .arg(arg!([FILE1]"File1").required(true))
-                .arg(arg!([FILE2]"File2").required(true)),
+                .arg(arg!([FILE2]"File2").required(true))
+                .arg(arg!(-U --unified <N> "Number of context lines around each change").required(false).value_parser(clap::value_parser!(usize)).default_value("3"))
+                .arg(arg!(--"diff-algorithm" <ALGORITHM> "The line diff algorithm").required(false).value_parser(["myers", "patience", "histogram"]).default_value("myers")),
         )
         .subcommand(
             Command::new("diff-commit")
                 .about("Diff between two commits")
-                .arg(arg!([HASH1]"Firs commit hash").required(true))
-                .arg(arg!([HASH2]"Second commit hash").required(true)),
+                .arg(arg!([HASH1]"First commit, any revision such as HEAD~1").required(true))
+                .arg(arg!([HASH2]"Second commit, any revision").required(true))
+                .arg(arg!(-U --unified <N> "Number of context lines around each change").required(false).value_parser(clap::value_parser!(usize)).default_value("3"))
+                .arg(arg!(--"diff-algorithm" <ALGORITHM> "The line diff algorithm").required(false).value_parser(["myers", "patience", "histogram"]).default_value("myers")),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Diff between current commit and previous one.")
+                .arg(arg!(-U --unified <N> "Number of context lines around each change").required(false).value_parser(clap::value_parser!(usize)).default_value("3"))
+                .arg(arg!(--"diff-algorithm" <ALGORITHM> "The line diff algorithm").required(false).value_parser(["myers", "patience", "histogram"]).default_value("myers")),
         )
-        .subcommand(Command::new("diff").about("Diff between current commit and previous one."))
         .subcommand(
             Command::new("diff-branch")
                 .about("Diff between two branches")
                 .arg(arg!([BRANCH1]"First branch name"))
-                .arg(arg!([BRANCH2]"Second branch name")),
+                .arg(arg!([BRANCH2]"Second branch name"))
+                .arg(arg!(-U --unified <N> "Number of context lines around each change").required(false).value_parser(clap::value_parser!(usize)).default_value("3"))
+                .arg(arg!(--"diff-algorithm" <ALGORITHM> "The line diff algorithm").required(false).value_parser(["myers", "patience", "histogram"]).default_value("myers")),
         )
         .subcommand(
             Command::new("ff-merge")
@@ -81,14 +141,136 @@ fn cli() -> Command {
                 .about("Three way merge between two branches")
                 .arg(arg!([BRANCH1]"First branch name").required(true))
                 .arg(arg!([BRANCH2]"Second branch name").required(true))
-                .arg(arg!(-a --"auto-resolve" "Automatically resolve conflicts, the file will be overwrite with the file in the branch you want to merge to.").required(false))
+                .arg(arg!(-a --"auto-resolve" "Resolve conflicting changes with the version of the branch that is merged in").required(false))
                 )
         .subcommand(
             Command::new("merge")
                 .about("Merge between the current branch and another, the merge algorithm will be choosen by the program.")
-                .arg(arg!([BRANCH]"Branch name").required(true))
-                .arg(arg!(-a --"auto-resolve" "Automatically resolve conflicts, the file will be overwrite with the file in the branch you want to merge to.").required(false))
+                .arg(arg!([BRANCH]"Branch name").required_unless_present_any(["continue", "abort"]))
+                .arg(arg!(-a --"auto-resolve" "Resolve conflicting changes with the version of the branch that is merged in").required(false))
+                .arg(arg!(--continue "Create the merge commit once the conflicts are resolved and added").required(false).conflicts_with_all(["BRANCH", "abort"]))
+                .arg(arg!(--abort "Give up a conflicted merge and go back to the commit before it").required(false).conflicts_with("BRANCH"))
+                )
+        .subcommand(
+            Command::new("merge-base")
+                .about("Find the best common ancestors of two commits, exits with 1 when there is none")
+                .arg(arg!([REV1]"First commit").required(true))
+                .arg(arg!([REV2]"Second commit").required(true))
+                .arg(arg!(-a --all "Print every best common ancestor instead of one").required(false))
+                .arg(arg!(--"is-ancestor" "Print nothing, exit with 0 when REV1 is an ancestor of REV2 and 1 otherwise").required(false).conflicts_with("all"))
+        )
+        .subcommand(
+            Command::new("reset")
+                .about("Move the current branch to a commit, or unstage files")
+                .arg(arg!([ARGS]... "A commit, HEAD by default, then paths to unstage").required(false))
+                .arg(arg!(--soft "Only move the branch, the index and the working tree are kept").required(false))
+                .arg(arg!(--mixed "Also rebuild the index from the commit, the default").required(false).conflicts_with("soft"))
+                .arg(arg!(--hard "Also overwrite the working tree with the commit").required(false).conflicts_with_all(["soft", "mixed"])),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Restore files in the working tree or the index from the index or a commit")
+                .arg(arg!(<PATHS>... "Files, directories or glob patterns to restore"))
+                .arg(arg!(-s --source <REV> "Commit to take the files from").required(false))
+                .arg(arg!(-S --staged "Restore the index, from HEAD by default").required(false))
+                .arg(arg!(-W --worktree "Restore the working tree, the default without --staged").required(false)),
+        )
+        .subcommand(
+            Command::new("rm")
+                .about("Remove files from the index and the working tree")
+                .arg(arg!(<PATHS>... "Files or directories to remove"))
+                .arg(arg!(--cached "Only remove them from the index, the files are kept").required(false))
+                .arg(arg!(-f --force "Remove them even when their changes would be lost").required(false)),
+        )
+        .subcommand(
+            Command::new("mv")
+                .about("Move or rename a tracked file or directory")
+                .arg(arg!(<SOURCE> "File or directory to move"))
+                .arg(arg!(<DESTINATION> "New path, or an existing directory to move it into")),
+        )
+        .subcommand(
+            Command::new("stash")
+                .about("Put local changes aside and bring them back later, push by default")
+                .arg(arg!(-m --message <MESSAGE> "Description of the stash when pushing").required(false))
+                .subcommand(
+                    Command::new("push")
+                        .about("Save local changes to tracked files and go back to HEAD")
+                        .arg(arg!(-m --message <MESSAGE> "Description of the stash").required(false)),
+                )
+                .subcommand(Command::new("list").about("List the stashes, the latest first"))
+                .subcommand(
+                    Command::new("show")
+                        .about("Show the changes a stash records")
+                        .arg(arg!([STASH]"stash@{n}, the latest by default").required(false)),
+                )
+                .subcommand(
+                    Command::new("apply")
+                        .about("Merge the changes of a stash into the working tree")
+                        .arg(arg!([STASH]"stash@{n}, the latest by default").required(false)),
                 )
+                .subcommand(
+                    Command::new("pop")
+                        .about("Apply a stash and drop it when it applied cleanly")
+                        .arg(arg!([STASH]"stash@{n}, the latest by default").required(false)),
+                )
+                .subcommand(
+                    Command::new("drop")
+                        .about("Forget a stash")
+                        .arg(arg!([STASH]"stash@{n}, the latest by default").required(false)),
+                ),
+        )
+        .subcommand(
+            Command::new("cherry-pick")
+                .about("Apply the changes of existing commits onto HEAD as new commits")
+                .arg(arg!([REVS]... "Commits to pick, in order").required_unless_present_any(["continue", "abort"]))
+                .arg(arg!(--continue "Commit the resolved conflicts and go on with the remaining commits").required(false))
+                .arg(arg!(--abort "Go back to where the sequence started").required(false).conflicts_with("continue")),
+        )
+        .subcommand(
+            Command::new("revert")
+                .about("Undo existing commits with new commits applying their inverse")
+                .arg(arg!([REVS]... "Commits to revert, in order").required_unless_present_any(["continue", "abort"]))
+                .arg(arg!(--continue "Commit the resolved conflicts and go on with the remaining commits").required(false))
+                .arg(arg!(--abort "Go back to where the sequence started").required(false).conflicts_with("continue")),
+        )
+        .subcommand(
+            Command::new("reflog")
+                .about("Show where HEAD or a branch pointed after each of its updates")
+                .arg(arg!([REF]"HEAD, a branch or a full ref name, HEAD by default").required(false)),
+        )
+        .subcommand(
+            Command::new("repack")
+                .about("Pack loose objects, storing similar objects as deltas against each other")
+                .arg(arg!(-a --all "Also repack the objects of the existing packs into the new one").required(false))
+                .arg(arg!(-d --delete "Remove loose objects and old packs made redundant by the new pack").required(false))
+                .arg(arg!(--window <N> "Number of objects to try as delta bases").required(false).value_parser(clap::value_parser!(usize)))
+                .arg(arg!(--depth <N> "Maximum length of a delta chain").required(false).value_parser(clap::value_parser!(usize)))
+        )
+        .subcommand(
+            Command::new("fsck")
+                .about("Verify the connectivity and validity of the objects and refs, exits with 1 when a problem is found. Dangling objects are only reported"),
+        )
+        .subcommand(
+            Command::new("gc")
+                .about("Remove unreachable objects and pack the reachable ones")
+                .arg(arg!(-n --"dry-run" "Only report what would be removed or packed").required(false))
+                .arg(arg!(--prune <EXPIRE> "Grace period for unreachable objects: now, never or a number followed by s, m, h, d or w").required(false).default_value(commands::gc::DEFAULT_EXPIRE))
+        )
+        .subcommand(
+            Command::new("prune")
+                .about("Remove unreachable loose objects")
+                .arg(arg!(-n --"dry-run" "Only report what would be removed").required(false))
+                .arg(arg!(--expire <EXPIRE> "Grace period for unreachable objects: now, never or a number followed by s, m, h, d or w").required(false).default_value(commands::gc::DEFAULT_EXPIRE))
+        )
+}
+
+// -U and --diff-algorithm, both have defaults and the algorithm is checked by clap
+fn diff_options(sub_matches: &ArgMatches) -> commands::diff::DiffOptions {
+    let algorithm = sub_matches.get_one::<String>("diff-algorithm").unwrap();
+    commands::diff::DiffOptions {
+        algorithm: others::line_diff::DiffAlgorithm::parse(algorithm).unwrap(),
+        context: *sub_matches.get_one::<usize>("unified").unwrap(),
+    }
 }
 
 fn main() {
@@ -97,8 +279,8 @@ fn main() {
         Some(("diff-files", sub_matches)) => {
             let file1 = sub_matches.get_one::<String>("FILE1");
             let file2 = sub_matches.get_one::<String>("FILE2");
-            if file1.is_some() && file2.is_some() {
-                match commands::diff::diff_between_files(file1.unwrap(), file2.unwrap()) {
+            if let (Some(file1), Some(file2)) = (file1, file2) {
+                match commands::diff::diff_between_files(file1, file2, &diff_options(sub_matches)) {
                     Ok(ans) => {
                         println!("{}", ans);
                     }
@@ -131,14 +313,42 @@ fn main() {
         Some(("merge", sub_matches)) => {
             let branch1 = sub_matches.get_one::<String>("BRANCH");
             let flag = sub_matches.get_flag("auto-resolve");
-            if let Err(e) = commands::merge::merge(branch1.unwrap(), flag) {
+            let result = if sub_matches.get_flag("continue") {
+                commands::merge::merge_continue(None, &Default::default())
+            } else if sub_matches.get_flag("abort") {
+                commands::merge::merge_abort()
+            } else {
+                commands::merge::merge(branch1.unwrap(), flag)
+            };
+            if let Err(e) = result {
                 eprintln!("Err: {}", e);
             }
         }
+        Some(("merge-base", sub_matches)) => {
+            let rev1 = sub_matches.get_one::<String>("REV1");
+            let rev2 = sub_matches.get_one::<String>("REV2");
+            match commands::merge_base::merge_base_command(
+                rev1.unwrap(),
+                rev2.unwrap(),
+                sub_matches.get_flag("all"),
+                sub_matches.get_flag("is-ancestor"),
+            ) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
         Some(("diff-commit", sub_matches)) => {
             let hash1 = sub_matches.get_one::<String>("HASH1");
             let hash2 = sub_matches.get_one::<String>("HASH2");
-            match commands::diff::diff_between_commits(hash1.unwrap(), hash2.unwrap()) {
+            match commands::diff::diff_between_revisions(
+                hash1.unwrap(),
+                hash2.unwrap(),
+                &diff_options(sub_matches),
+            ) {
                 Ok(ans) => {
                     println!("{}", ans);
                 }
@@ -147,16 +357,21 @@ fn main() {
                 }
             }
         }
-        Some(("diff", _)) => {
-            if let Err(err) = commands::diff::diff_between_current_last_commit() {
+        Some(("diff", sub_matches)) => {
+            if let Err(err) =
+                commands::diff::diff_between_current_last_commit(&diff_options(sub_matches))
+            {
                 println!("{}", err);
             }
         }
         Some(("diff-branch", sub_matches)) => {
             let hash1 = sub_matches.get_one::<String>("BRANCH1");
             let hash2 = sub_matches.get_one::<String>("BRANCH2");
-            if let Err(err) = commands::diff::diff_between_branches(hash1.unwrap(), hash2.unwrap())
-            {
+            if let Err(err) = commands::diff::diff_between_branches(
+                hash1.unwrap(),
+                hash2.unwrap(),
+                &diff_options(sub_matches),
+            ) {
                 println!("{}", err);
             }
         }
@@ -171,6 +386,31 @@ fn main() {
                 eprintln!("Erorr: {}", err);
             }
         }
+        Some(("config", sub_matches)) => {
+            let key = sub_matches.get_one::<String>("KEY");
+            let value = sub_matches.get_one::<String>("VALUE");
+            let list = sub_matches.get_flag("list");
+            if let Err(err) = commands::config::config_command(key, value, list) {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("log", sub_matches)) => {
+            let rev = sub_matches.get_one::<String>("REV");
+            let options = commands::log::LogOptions {
+                oneline: sub_matches.get_flag("oneline"),
+                max_count: sub_matches.get_one::<usize>("max-count").copied(),
+                first_parent: sub_matches.get_flag("first-parent"),
+                order: if sub_matches.get_flag("topo-order") {
+                    commands::log::LogOrder::Topo
+                } else {
+                    commands::log::LogOrder::Date
+                },
+                graph: sub_matches.get_flag("graph"),
+            };
+            if let Err(err) = commands::log::log_command(rev.map(|r| r.as_str()), &options) {
+                eprintln!("Error: {}", err);
+            }
+        }
         Some(("ls-files", _)) => {
             if let Err(err) = commands::ls_files::get_info() {
                 eprintln!("Error: {}", err);
@@ -178,7 +418,8 @@ fn main() {
         }
         Some(("dec-object", sub_matches)) => {
             let hash = sub_matches.get_one::<String>("NAME");
-            if let Err(err) = commands::dec_object::get_info(hash.unwrap()) {
+            let show_type = sub_matches.get_flag("type");
+            if let Err(err) = commands::dec_object::get_info(hash.unwrap(), show_type) {
                 eprintln!("Error: {}", err);
             }
         }
@@ -190,16 +431,26 @@ fn main() {
 
         Some(("commit", sub_matches)) => {
             let msg = sub_matches.get_one::<String>("NAME");
-            if let Err(err) = commands::commit::commit_command(msg.unwrap()) {
+            let overrides = SignatureOverrides {
+                author: sub_matches.get_one::<String>("author").cloned(),
+                date: sub_matches.get_one::<String>("date").cloned(),
+            };
+            if let Err(err) = commands::commit::commit_command(msg.unwrap(), &overrides) {
                 eprintln!("Error: {}", err);
             }
         }
         Some(("commit-tree", sub_matches)) => {
             let tree_hash = sub_matches.get_one::<String>("TREE_HASH");
             let commit_msg = sub_matches.get_one::<String>("COMMIT_MSG");
-            if let Err(err) =
-                commands::commit::commit_tree_command(tree_hash.unwrap(), commit_msg.unwrap())
-            {
+            let overrides = SignatureOverrides {
+                author: sub_matches.get_one::<String>("author").cloned(),
+                date: sub_matches.get_one::<String>("date").cloned(),
+            };
+            if let Err(err) = commands::commit::commit_tree_command(
+                tree_hash.unwrap(),
+                commit_msg.unwrap(),
+                &overrides,
+            ) {
                 eprintln!("Error: {}", err);
             }
         }
@@ -210,13 +461,225 @@ fn main() {
         }
         Some(("branch", sub_matches)) => {
             let branch_name = sub_matches.get_one::<String>("NAME");
-            if let Err(err) = commands::branch::branch_command(branch_name.unwrap()) {
+            let start_point = sub_matches.get_one::<String>("START_POINT");
+            let result = match branch_name {
+                None => commands::branch::list_branches(),
+                Some(name)
+                    if sub_matches.get_flag("delete") || sub_matches.get_flag("force_delete") =>
+                {
+                    commands::branch::delete_branch(name, sub_matches.get_flag("force_delete"))
+                }
+                Some(name) if sub_matches.get_flag("move") => match start_point {
+                    Some(new_name) => commands::branch::rename_branch(Some(name), new_name),
+                    None => commands::branch::rename_branch(None, name),
+                },
+                Some(name) => {
+                    commands::branch::branch_command(name, start_point.map(|s| s.as_str()))
+                }
+            };
+            if let Err(err) = result {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("tag", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("NAME");
+            let result = match name {
+                None => commands::tag::list_tags(sub_matches.get_flag("show_messages")),
+                Some(name) if sub_matches.get_flag("delete") => commands::tag::delete_tag(name),
+                Some(name) => {
+                    let overrides = SignatureOverrides {
+                        author: None,
+                        date: sub_matches.get_one::<String>("date").cloned(),
+                    };
+                    commands::tag::tag_command(
+                        name,
+                        sub_matches.get_one::<String>("REV").map(|s| s.as_str()),
+                        sub_matches.get_one::<String>("message").map(|s| s.as_str()),
+                        sub_matches.get_flag("force"),
+                        &overrides,
+                    )
+                }
+            };
+            if let Err(err) = result {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("switch", sub_matches)) => {
+            let branch = sub_matches.get_one::<String>("BRANCH");
+            let create = sub_matches.get_one::<String>("create");
+            if let Err(err) = commands::checkout::switch_command(
+                branch.map(|s| s.as_str()),
+                create.map(|s| s.as_str()),
+            ) {
                 eprintln!("Error: {}", err);
             }
         }
         Some(("checkout", sub_matches)) => {
             let branch_name = sub_matches.get_one::<String>("NAME");
-            if let Err(err) = commands::checkout::checkout(branch_name.unwrap()) {
+            let mode = if sub_matches.get_flag("force") {
+                commands::checkout::CheckoutMode::Force
+            } else if sub_matches.get_flag("merge") {
+                commands::checkout::CheckoutMode::Merge
+            } else {
+                commands::checkout::CheckoutMode::Safe
+            };
+            let result = match sub_matches.get_one::<String>("new-branch") {
+                Some(new_branch) => commands::checkout::checkout_new_branch(
+                    new_branch,
+                    branch_name.map(|s| s.as_str()),
+                    mode,
+                ),
+                None => commands::checkout::checkout_with_mode(branch_name.unwrap(), mode),
+            };
+            if let Err(err) = result {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("reset", sub_matches)) => {
+            let args: Vec<String> = sub_matches
+                .get_many::<String>("ARGS")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            let mode = if sub_matches.get_flag("soft") {
+                Some(commands::reset::ResetMode::Soft)
+            } else if sub_matches.get_flag("mixed") {
+                Some(commands::reset::ResetMode::Mixed)
+            } else if sub_matches.get_flag("hard") {
+                Some(commands::reset::ResetMode::Hard)
+            } else {
+                None
+            };
+            if let Err(err) = commands::reset::reset_command(&args, mode) {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("restore", sub_matches)) => {
+            let paths: Vec<String> = sub_matches
+                .get_many::<String>("PATHS")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            let source = sub_matches.get_one::<String>("source");
+            if let Err(err) = commands::restore::restore_command(
+                &paths,
+                source.map(|s| s.as_str()),
+                sub_matches.get_flag("staged"),
+                sub_matches.get_flag("worktree"),
+            ) {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("rm", sub_matches)) => {
+            let paths: Vec<String> = sub_matches
+                .get_many::<String>("PATHS")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            if let Err(err) = commands::rm::rm_command(
+                &paths,
+                sub_matches.get_flag("cached"),
+                sub_matches.get_flag("force"),
+            ) {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("mv", sub_matches)) => {
+            let source = sub_matches.get_one::<String>("SOURCE");
+            let destination = sub_matches.get_one::<String>("DESTINATION");
+            if let Err(err) = commands::mv::mv_command(source.unwrap(), destination.unwrap()) {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("stash", sub_matches)) => {
+            let stash = |matches: &clap::ArgMatches| {
+                matches
+                    .get_one::<String>("STASH")
+                    .map(|s| s.as_str().to_string())
+            };
+            let result = match sub_matches.subcommand() {
+                Some(("push", push_matches)) => commands::stash::stash_push(
+                    push_matches
+                        .get_one::<String>("message")
+                        .map(|s| s.as_str()),
+                ),
+                Some(("list", _)) => commands::stash::stash_list(),
+                Some(("show", matches)) => commands::stash::stash_show(stash(matches).as_deref()),
+                Some(("apply", matches)) => {
+                    commands::stash::stash_apply(stash(matches).as_deref()).map(|_| ())
+                }
+                Some(("pop", matches)) => commands::stash::stash_pop(stash(matches).as_deref()),
+                Some(("drop", matches)) => commands::stash::stash_drop(stash(matches).as_deref()),
+                _ => commands::stash::stash_push(
+                    sub_matches.get_one::<String>("message").map(|s| s.as_str()),
+                ),
+            };
+            if let Err(err) = result {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some((name @ ("cherry-pick" | "revert"), sub_matches)) => {
+            let action = if name == "revert" {
+                commands::sequencer::Action::Revert
+            } else {
+                commands::sequencer::Action::Pick
+            };
+            let revs: Vec<String> = sub_matches
+                .get_many::<String>("REVS")
+                .unwrap_or_default()
+                .cloned()
+                .collect();
+            let result = if sub_matches.get_flag("continue") {
+                commands::sequencer::sequence_continue()
+            } else if sub_matches.get_flag("abort") {
+                commands::sequencer::sequence_abort()
+            } else {
+                commands::sequencer::sequence_command(action, &revs)
+            };
+            if let Err(err) = result {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("reflog", sub_matches)) => {
+            let name = sub_matches.get_one::<String>("REF");
+            if let Err(err) = commands::reflog::reflog_command(name.map(|s| s.as_str())) {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("repack", sub_matches)) => {
+            let all = sub_matches.get_flag("all");
+            let delete = sub_matches.get_flag("delete");
+            let window = sub_matches
+                .get_one::<usize>("window")
+                .copied()
+                .unwrap_or(objects::pack::DEFAULT_WINDOW);
+            let depth = sub_matches
+                .get_one::<usize>("depth")
+                .copied()
+                .unwrap_or(objects::pack::DEFAULT_DEPTH);
+            if let Err(err) = commands::repack::repack_command(all, delete, window, depth) {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("fsck", _)) => match commands::fsck::fsck_command() {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                std::process::exit(1);
+            }
+        },
+        Some(("gc", sub_matches)) => {
+            let dry_run = sub_matches.get_flag("dry-run");
+            let expire = sub_matches.get_one::<String>("prune");
+            if let Err(err) = commands::gc::gc_command(dry_run, expire.unwrap()) {
+                eprintln!("Error: {}", err);
+            }
+        }
+        Some(("prune", sub_matches)) => {
+            let dry_run = sub_matches.get_flag("dry-run");
+            let expire = sub_matches.get_one::<String>("expire");
+            if let Err(err) = commands::gc::prune_command(dry_run, expire.unwrap()) {
                 eprintln!("Error: {}", err);
             }
         }
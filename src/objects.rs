@@ -1,3 +1,7 @@
 pub mod blob;
 pub mod commit;
+pub mod delta;
+pub mod pack;
+pub mod store;
+pub mod tag;
 pub mod tree;
@@ -1,27 +1,257 @@
-use anyhow::Result;
-use std::fs;
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::others::compression;
-use crate::others::hash_function::calculate_hash;
+use crate::objects::store::{self, ObjectKind};
+use crate::others::config;
 
 pub struct Commit {
     pub id: String, // commit hash_function
     pub content: CommitContent,
 }
 
+/*
+ * Who made a change and when, serialized like git:
+ *     Jane Doe <jane@example.com> 1700000000 +0200
+ * */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub time: i64,      // seconds since the unix epoch
+    pub tz_offset: i32, // minutes east of UTC
+}
+
+// Values given on the command line win over the environment and the config.
+#[derive(Default)]
+pub struct SignatureOverrides {
+    pub author: Option<String>, // "Name <email>"
+    pub date: Option<String>,   // "<epoch> [+hhmm]", used for author and committer
+}
+
+pub fn parse_identity(identity: &str) -> Result<(String, String)> {
+    let (name, rest) = identity
+        .split_once('<')
+        .ok_or_else(|| anyhow!("Invalid identity '{}', expected 'Name <email>'", identity))?;
+    let email = rest
+        .strip_suffix('>')
+        .ok_or_else(|| anyhow!("Invalid identity '{}', expected 'Name <email>'", identity))?;
+    let name = name.trim();
+    if name.is_empty() || email.contains(['<', '>']) {
+        return Err(anyhow!(
+            "Invalid identity '{}', expected 'Name <email>'",
+            identity
+        ));
+    }
+    Ok((name.to_string(), email.trim().to_string()))
+}
+
+fn parse_tz(tz: &str) -> Result<i32> {
+    let invalid = || anyhow!("Invalid timezone offset '{}', expected +hhmm or -hhmm", tz);
+    if tz.len() != 5 || !tz[1..].chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let hours: i32 = tz[1..3].parse()?;
+    let minutes: i32 = tz[3..5].parse()?;
+    match &tz[0..1] {
+        "+" => Ok(hours * 60 + minutes),
+        "-" => Ok(-(hours * 60 + minutes)),
+        _ => Err(invalid()),
+    }
+}
+
+pub fn format_tz(tz_offset: i32) -> String {
+    let sign = if tz_offset < 0 { '-' } else { '+' };
+    let minutes = tz_offset.abs();
+    format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+// The offset of the local timezone at that time, in minutes east of UTC.
+fn local_tz_offset(time: i64) -> i32 {
+    let time = time as libc::time_t;
+    // localtime_r only fills the tm it is given, a zeroed tm is a valid one
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    (tm.tm_gmtoff / 60) as i32
+}
+
+// "<epoch>" or "<epoch> <+hhmm>"
+pub fn parse_date(date: &str) -> Result<(i64, i32)> {
+    let mut parts = date.split_whitespace();
+    let time = parts
+        .next()
+        .ok_or_else(|| anyhow!("Empty date"))?
+        .parse()
+        .with_context(|| format!("Invalid date '{}', expected seconds since the epoch", date))?;
+    let tz_offset = match parts.next() {
+        Some(tz) => parse_tz(tz)?,
+        None => 0,
+    };
+    if parts.next().is_some() {
+        return Err(anyhow!("Invalid date '{}'", date));
+    }
+    Ok((time, tz_offset))
+}
+
+// Days since the epoch to (year, month, day), Howard Hinnant's civil_from_days.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+impl Signature {
+    // "Tue Nov 14 22:13:20 2023 +0200", in the timezone the signature was made in
+    pub fn format_date(&self) -> String {
+        const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let local = self.time + self.tz_offset as i64 * 60;
+        let days = local.div_euclid(86400);
+        let seconds = local.rem_euclid(86400);
+        let (year, month, day) = civil_from_days(days);
+        format!(
+            "{} {} {} {:02}:{:02}:{:02} {} {}",
+            DAYS[(days + 4).rem_euclid(7) as usize],
+            MONTHS[month as usize - 1],
+            day,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60,
+            year,
+            format_tz(self.tz_offset)
+        )
+    }
+    pub fn pretty_print(&self) -> String {
+        format!(
+            "{} <{}> {} {}",
+            self.name,
+            self.email,
+            self.time,
+            format_tz(self.tz_offset)
+        )
+    }
+    pub fn from_pretty_print(line: &str) -> Result<Self> {
+        let end = line
+            .rfind('>')
+            .ok_or_else(|| anyhow!("Malformed signature: {}", line))?;
+        let (name, email) = parse_identity(&line[..=end])?;
+        let (time, tz_offset) = parse_date(line[end + 1..].trim())
+            .with_context(|| format!("Malformed signature: {}", line))?;
+        Ok(Self {
+            name,
+            email,
+            time,
+            tz_offset,
+        })
+    }
+
+    /*
+     * The identity comes from, in order: the --author override (author only),
+     * RVCS_<ROLE>_NAME / RVCS_<ROLE>_EMAIL, then user.name / user.email in .vcs/config.
+     * The date comes from --date, RVCS_<ROLE>_DATE or the current time.
+     * */
+    fn resolve(role: &str, overrides: &SignatureOverrides) -> Result<Self> {
+        let (mut name, mut email) = (None, None);
+        if role == "AUTHOR" {
+            if let Some(author) = &overrides.author {
+                let (n, e) = parse_identity(author)?;
+                name = Some(n);
+                email = Some(e);
+            }
+        }
+        let name = match name.or_else(|| env::var(format!("RVCS_{}_NAME", role)).ok()) {
+            Some(name) => name,
+            None => config::get("user.name")?.ok_or_else(|| {
+                anyhow!("Identity unknown, set it with `rvcs config user.name \"Your Name\"` or RVCS_{}_NAME", role)
+            })?,
+        };
+        let email = match email.or_else(|| env::var(format!("RVCS_{}_EMAIL", role)).ok()) {
+            Some(email) => email,
+            None => config::get("user.email")?.ok_or_else(|| {
+                anyhow!("Identity unknown, set it with `rvcs config user.email you@example.com` or RVCS_{}_EMAIL", role)
+            })?,
+        };
+        let date = overrides
+            .date
+            .clone()
+            .or_else(|| env::var(format!("RVCS_{}_DATE", role)).ok());
+        let (time, tz_offset) = match date {
+            Some(date) => parse_date(&date)?,
+            None => {
+                let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+                (time, local_tz_offset(time))
+            }
+        };
+        if name.contains(['<', '>', '\n']) || email.contains(['<', '>', '\n']) {
+            return Err(anyhow!("Identity may not contain '<', '>' or newlines"));
+        }
+        Ok(Self {
+            name,
+            email,
+            time,
+            tz_offset,
+        })
+    }
+
+    // The tagger of a new annotated tag, resolved like a committer.
+    pub fn for_tag(overrides: &SignatureOverrides) -> Result<Self> {
+        Self::resolve("COMMITTER", overrides)
+    }
+
+    // Who updated a ref, the log is kept even when no identity is configured.
+    pub fn for_reflog() -> Self {
+        Self::resolve("COMMITTER", &SignatureOverrides::default()).unwrap_or_else(|_| Self {
+            name: String::from("unknown"),
+            email: String::from("unknown"),
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs() as i64)
+                .unwrap_or(0),
+            tz_offset: 0,
+        })
+    }
+
+    // Author and committer for a new commit.
+    pub fn for_commit(overrides: &SignatureOverrides) -> Result<(Self, Self)> {
+        Ok((
+            Self::resolve("AUTHOR", overrides)?,
+            Self::resolve("COMMITTER", overrides)?,
+        ))
+    }
+}
+
 pub struct CommitContent {
     pub tree: String,         // SHA1 of the tree object
     pub parents: Vec<String>, // SHA1(s) of parent commits
-    pub message: String,      // Commit message
+    pub author: Signature,
+    pub committer: Signature,
+    pub message: String, // Commit message
 }
 
 impl CommitContent {
-    pub fn new(tree: String, parents: Vec<String>, message: String) -> Self {
+    pub fn new(
+        tree: String,
+        parents: Vec<String>,
+        author: Signature,
+        committer: Signature,
+        message: String,
+    ) -> Self {
         CommitContent {
             tree,
             parents,
+            author,
+            committer,
             message,
         }
     }
@@ -31,6 +261,8 @@ impl CommitContent {
         for parent in &self.parents {
             commit_data.push_str(&format!("parent {}\n", parent));
         }
+        commit_data.push_str(&format!("author {}\n", self.author.pretty_print()));
+        commit_data.push_str(&format!("committer {}\n", self.committer.pretty_print()));
 
         commit_data.push_str(&format!("\n{}\n", self.message));
 
@@ -40,6 +272,8 @@ impl CommitContent {
         let lines = content.lines();
         let mut tree = String::new();
         let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
         let mut message = String::new();
         let mut in_message = false;
 
@@ -53,6 +287,10 @@ impl CommitContent {
                 tree = stripped.to_string();
             } else if let Some(stripped) = line.strip_prefix("parent ") {
                 parents.push(stripped.to_string());
+            } else if let Some(stripped) = line.strip_prefix("author ") {
+                author = Some(Signature::from_pretty_print(stripped)?);
+            } else if let Some(stripped) = line.strip_prefix("committer ") {
+                committer = Some(Signature::from_pretty_print(stripped)?);
             } else if line.trim().is_empty() {
                 in_message = true;
             } else {
@@ -71,43 +309,31 @@ impl CommitContent {
         Ok(Self {
             tree,
             parents,
+            author: author.ok_or_else(|| anyhow!("Missing author in commit content."))?,
+            committer: committer.ok_or_else(|| anyhow!("Missing committer in commit content."))?,
             message,
         })
     }
 }
 
 impl Commit {
-    pub fn new(tree: String, parents: Vec<String>, message: String) -> Self {
-        let content = CommitContent::new(tree, parents, message);
-        let content_as_bytes = content.pretty_print().into_bytes();
-        let id = calculate_hash(&content_as_bytes);
+    pub fn new(
+        tree: String,
+        parents: Vec<String>,
+        author: Signature,
+        committer: Signature,
+        message: String,
+    ) -> Self {
+        let content = CommitContent::new(tree, parents, author, committer, message);
+        let id = store::hash_object(ObjectKind::Commit, content.pretty_print().as_bytes());
         Self { id, content }
     }
     pub fn create_commit(&self) -> Result<()> {
-        let vcs_objects_path: PathBuf = Path::new(".vcs").join("objects");
-
-        if !vcs_objects_path.exists() {
-            return Err(anyhow::anyhow!("The repository is not initialized"));
-        }
-
-        // Serialize commit object
-        let commit_content = self.content.pretty_print();
-        let header = format!("commit{}\0", commit_content.len());
-        let mut content = header.into_bytes();
-        content.extend_from_slice(commit_content.as_bytes());
-        let compressed_content = compression::compress(commit_content.as_bytes())?;
-
-        // Create the object subfolder
-        let subfolder = vcs_objects_path.join(&self.id[0..2]);
-        if !subfolder.exists() {
-            fs::create_dir_all(&subfolder)?;
-        }
-
-        // Write the commit object to the file
-        let file_path = subfolder.join(&self.id[2..]);
-        let mut file = fs::File::create(file_path)?;
-        file.write_all(&compressed_content)?;
-
+        store::write_object(ObjectKind::Commit, self.content.pretty_print().as_bytes())?;
         Ok(())
     }
+    pub fn read(commit_hash: &str) -> Result<CommitContent> {
+        let content = store::read_text(commit_hash, ObjectKind::Commit)?;
+        CommitContent::from_pretty_print(&content)
+    }
 }
@@ -0,0 +1,242 @@
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/*
+ * Deltas use the same instruction set as git:
+ *     <base size varint> <result size varint> <instructions...>
+ * An instruction with the high bit set copies a range of the base, the bits
+ * 0x01..0x08 say which offset bytes follow and 0x10..0x40 which size bytes.
+ * Any other non zero byte inserts that many literal bytes from the delta.
+ * */
+
+const BLOCK: usize = 16;
+const MAX_INSERT: usize = 0x7f;
+const MAX_COPY: usize = 0xff_ffff;
+
+pub fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub fn read_varint(data: &[u8], pos: &mut usize) -> Result<usize> {
+    let mut value = 0usize;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*pos).ok_or_else(|| anyhow!("Truncated varint"))?;
+        *pos += 1;
+        if shift > 56 {
+            return Err(anyhow!("Varint is too long"));
+        }
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+fn flush_insert(out: &mut Vec<u8>, insert: &mut Vec<u8>) {
+    for chunk in insert.chunks(MAX_INSERT) {
+        out.push(chunk.len() as u8);
+        out.extend_from_slice(chunk);
+    }
+    insert.clear();
+}
+
+fn push_copy(out: &mut Vec<u8>, mut offset: usize, mut size: usize) {
+    while size > 0 {
+        let chunk = size.min(MAX_COPY);
+        let mut cmd = 0x80u8;
+        let mut args = Vec::new();
+        for i in 0..4 {
+            let byte = ((offset >> (8 * i)) & 0xff) as u8;
+            if byte != 0 {
+                cmd |= 1 << i;
+                args.push(byte);
+            }
+        }
+        for i in 0..3 {
+            let byte = ((chunk >> (8 * i)) & 0xff) as u8;
+            if byte != 0 {
+                cmd |= 0x10 << i;
+                args.push(byte);
+            }
+        }
+        out.push(cmd);
+        out.extend_from_slice(&args);
+        offset += chunk;
+        size -= chunk;
+    }
+}
+
+pub fn create_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(&mut out, base.len());
+    write_varint(&mut out, target.len());
+
+    // index every aligned block of the base by its content
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+    for start in (0..base.len().saturating_sub(BLOCK - 1)).step_by(BLOCK) {
+        blocks.entry(&base[start..start + BLOCK]).or_insert(start);
+    }
+
+    let mut insert = Vec::new();
+    let mut i = 0;
+    while i < target.len() {
+        let found = if i + BLOCK <= target.len() {
+            blocks.get(&target[i..i + BLOCK]).copied()
+        } else {
+            None
+        };
+        match found {
+            Some(mut start) => {
+                let mut len = BLOCK;
+                while start + len < base.len()
+                    && i + len < target.len()
+                    && base[start + len] == target[i + len]
+                {
+                    len += 1;
+                }
+                // extend backwards over bytes that were queued for insertion
+                while start > 0 && !insert.is_empty() && base[start - 1] == target[i - 1] {
+                    insert.pop();
+                    start -= 1;
+                    i -= 1;
+                    len += 1;
+                }
+                flush_insert(&mut out, &mut insert);
+                push_copy(&mut out, start, len);
+                i += len;
+            }
+            None => {
+                insert.push(target[i]);
+                i += 1;
+            }
+        }
+    }
+    flush_insert(&mut out, &mut insert);
+    out
+}
+
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let mut pos = 0;
+    let base_size = read_varint(delta, &mut pos)?;
+    let result_size = read_varint(delta, &mut pos)?;
+    if base_size != base.len() {
+        return Err(anyhow!(
+            "Delta base size mismatch: expected {}, found {}",
+            base_size,
+            base.len()
+        ));
+    }
+
+    let mut result = Vec::with_capacity(result_size);
+    while pos < delta.len() {
+        let cmd = delta[pos];
+        pos += 1;
+        if cmd & 0x80 != 0 {
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for i in 0..4 {
+                if cmd & (1 << i) != 0 {
+                    let byte = *delta.get(pos).ok_or_else(|| anyhow!("Truncated delta"))?;
+                    offset |= (byte as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            for i in 0..3 {
+                if cmd & (0x10 << i) != 0 {
+                    let byte = *delta.get(pos).ok_or_else(|| anyhow!("Truncated delta"))?;
+                    size |= (byte as usize) << (8 * i);
+                    pos += 1;
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            let end = offset
+                .checked_add(size)
+                .filter(|&end| end <= base.len())
+                .ok_or_else(|| anyhow!("Delta copies outside of its base"))?;
+            result.extend_from_slice(&base[offset..end]);
+        } else if cmd != 0 {
+            let end = pos + cmd as usize;
+            if end > delta.len() {
+                return Err(anyhow!("Truncated delta"));
+            }
+            result.extend_from_slice(&delta[pos..end]);
+            pos = end;
+        } else {
+            return Err(anyhow!("Invalid delta instruction"));
+        }
+    }
+
+    if result.len() != result_size {
+        return Err(anyhow!(
+            "Delta result size mismatch: expected {}, found {}",
+            result_size,
+            result.len()
+        ));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(base: &[u8], target: &[u8]) {
+        let delta = create_delta(base, target);
+        assert_eq!(apply_delta(base, &delta).unwrap(), target);
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        for value in [0, 1, 127, 128, 300, 1 << 20, usize::MAX >> 1] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            let mut pos = 0;
+            assert_eq!(read_varint(&out, &mut pos).unwrap(), value);
+            assert_eq!(pos, out.len());
+        }
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let base: Vec<u8> = (0..4000).map(|i| (i * 7 % 251) as u8).collect();
+        let mut target = base[100..2500].to_vec();
+        target.extend_from_slice(b"something inserted in the middle");
+        target.extend_from_slice(&base[3000..]);
+        target.extend_from_slice(&base[..50]);
+        round_trip(&base, &target);
+        round_trip(&base, &base);
+        round_trip(b"short", b"not much longer");
+    }
+
+    #[test]
+    fn delta_copies_what_the_base_has() {
+        let base = vec![b'x'; 1000];
+        let delta = create_delta(&base, &base);
+        assert!(delta.len() < 16);
+    }
+
+    #[test]
+    fn empty_base_or_target() {
+        round_trip(b"", b"all of it is inserted");
+        round_trip(b"all of it is dropped", b"");
+        round_trip(b"", b"");
+    }
+
+    #[test]
+    fn wrong_base_is_refused() {
+        let delta = create_delta(b"the base", b"the target");
+        assert!(apply_delta(b"another base", &delta).is_err());
+    }
+}
@@ -0,0 +1,515 @@
+use crate::objects::delta;
+use crate::objects::store::{self, ObjectKind};
+use crate::objects::tree::TreeEntry;
+use crate::others::compression;
+use crate::others::hash_function::calculate_hash;
+use anyhow::{anyhow, Context, Result};
+use std::cell::{OnceCell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/*
+ * Packs live in .vcs/objects/pack as a pair of files:
+ *
+ * pack-<checksum>.pack
+ *     "PACK" | version u32 | object count u32
+ *     entries: type byte | size varint | [base hash, 20 bytes] | zlib data
+ *     SHA1 of everything above, 20 bytes
+ *
+ * pack-<checksum>.idx
+ *     "RIDX" | version u32 | object count u32
+ *     entries sorted by hash: hash, 20 bytes | offset in the pack u64
+ *     SHA1 of the pack, 20 bytes
+ *
+ * A delta entry stores the changes needed to rebuild the object from its base.
+ * */
+
+const PACK_MAGIC: &[u8; 4] = b"PACK";
+const IDX_MAGIC: &[u8; 4] = b"RIDX";
+const VERSION: u32 = 1;
+const IDX_ENTRY_LEN: usize = 28;
+
+const TYPE_COMMIT: u8 = 1;
+const TYPE_TREE: u8 = 2;
+const TYPE_BLOB: u8 = 3;
+const TYPE_TAG: u8 = 4;
+const TYPE_REF_DELTA: u8 = 7;
+
+pub const DEFAULT_WINDOW: usize = 10;
+pub const DEFAULT_DEPTH: usize = 50;
+
+pub struct PackObject {
+    pub hash: String,
+    pub kind: ObjectKind,
+    pub payload: Vec<u8>,
+}
+
+pub fn pack_dir() -> PathBuf {
+    store::objects_dir().join("pack")
+}
+
+fn kind_to_type(kind: ObjectKind) -> u8 {
+    match kind {
+        ObjectKind::Commit => TYPE_COMMIT,
+        ObjectKind::Tree => TYPE_TREE,
+        ObjectKind::Blob => TYPE_BLOB,
+        ObjectKind::Tag => TYPE_TAG,
+    }
+}
+
+fn type_to_kind(obj_type: u8) -> Result<ObjectKind> {
+    match obj_type {
+        TYPE_COMMIT => Ok(ObjectKind::Commit),
+        TYPE_TREE => Ok(ObjectKind::Tree),
+        TYPE_BLOB => Ok(ObjectKind::Blob),
+        TYPE_TAG => Ok(ObjectKind::Tag),
+        _ => Err(anyhow!("Unknown pack entry type: {}", obj_type)),
+    }
+}
+
+pub fn hash_to_bytes(hash: &str) -> Result<[u8; 20]> {
+    if hash.len() != 40 {
+        return Err(anyhow!("Invalid object hash: {}", hash));
+    }
+    let mut bytes = [0u8; 20];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hash[2 * i..2 * i + 2], 16)
+            .with_context(|| format!("Invalid object hash: {}", hash))?;
+    }
+    Ok(bytes)
+}
+
+pub fn bytes_to_hash(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32> {
+    let bytes = data
+        .get(pos..pos + 4)
+        .ok_or_else(|| anyhow!("Unexpected end of pack data"))?;
+    Ok(u32::from_be_bytes(bytes.try_into()?))
+}
+
+// Returns the idx files of every pack in the repository.
+pub fn list_packs() -> Result<Vec<PathBuf>> {
+    let dir = pack_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut packs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some("idx") {
+            packs.push(path);
+        }
+    }
+    packs.sort();
+    Ok(packs)
+}
+
+pub struct PackIndex {
+    pub pack_path: PathBuf,
+    data: Vec<u8>,
+    count: usize,
+}
+
+impl PackIndex {
+    pub fn open(idx_path: &Path) -> Result<Self> {
+        let data = fs::read(idx_path)
+            .with_context(|| format!("Failed to read pack index {}", idx_path.display()))?;
+        if data.len() < 12 || &data[0..4] != IDX_MAGIC {
+            return Err(anyhow!("Invalid pack index {}", idx_path.display()));
+        }
+        if read_u32(&data, 4)? != VERSION {
+            return Err(anyhow!("Unsupported pack index version"));
+        }
+        let count = read_u32(&data, 8)? as usize;
+        if data.len() != 12 + count * IDX_ENTRY_LEN + 20 {
+            return Err(anyhow!("Truncated pack index {}", idx_path.display()));
+        }
+        Ok(Self {
+            pack_path: idx_path.with_extension("pack"),
+            data,
+            count,
+        })
+    }
+
+    fn entry(&self, i: usize) -> (&[u8], u64) {
+        let start = 12 + i * IDX_ENTRY_LEN;
+        let hash = &self.data[start..start + 20];
+        let offset = u64::from_be_bytes(self.data[start + 20..start + 28].try_into().unwrap());
+        (hash, offset)
+    }
+
+    pub fn find(&self, hash: &[u8; 20]) -> Option<u64> {
+        let (mut low, mut high) = (0, self.count);
+        while low < high {
+            let mid = (low + high) / 2;
+            let (entry_hash, offset) = self.entry(mid);
+            match entry_hash.cmp(&hash[..]) {
+                std::cmp::Ordering::Equal => return Some(offset),
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+            }
+        }
+        None
+    }
+
+    pub fn hashes(&self) -> Vec<String> {
+        (0..self.count)
+            .map(|i| bytes_to_hash(self.entry(i).0))
+            .collect()
+    }
+}
+
+pub enum PackEntry {
+    Full(ObjectKind, Vec<u8>),
+    Delta { base: String, delta: Vec<u8> },
+}
+
+pub fn read_entry(pack: &[u8], offset: usize) -> Result<PackEntry> {
+    let obj_type = *pack
+        .get(offset)
+        .ok_or_else(|| anyhow!("Pack offset {} is out of range", offset))?;
+    let mut pos = offset + 1;
+    let size = delta::read_varint(pack, &mut pos)?;
+    let mut base = None;
+    if obj_type == TYPE_REF_DELTA {
+        let bytes = pack
+            .get(pos..pos + 20)
+            .ok_or_else(|| anyhow!("Unexpected end of pack data"))?;
+        base = Some(bytes_to_hash(bytes));
+        pos += 20;
+    }
+    let data = compression::decompress(&pack[pos..])?;
+    if data.len() != size {
+        return Err(anyhow!(
+            "Pack entry at offset {} has the wrong size",
+            offset
+        ));
+    }
+    match base {
+        Some(base) => Ok(PackEntry::Delta { base, delta: data }),
+        None => Ok(PackEntry::Full(type_to_kind(obj_type)?, data)),
+    }
+}
+
+// Resolved entries kept per pack, so objects sharing a delta chain rebuild it once.
+const BASE_CACHE_LIMIT: usize = 256;
+
+// An opened pack, its data is only read the first time an entry is needed.
+struct LoadedPack {
+    index: PackIndex,
+    data: OnceCell<Vec<u8>>,
+    resolved: RefCell<HashMap<u64, (ObjectKind, Vec<u8>)>>,
+}
+
+impl LoadedPack {
+    fn open(idx_path: &Path) -> Result<Self> {
+        Ok(LoadedPack {
+            index: PackIndex::open(idx_path)?,
+            data: OnceCell::new(),
+            resolved: RefCell::new(HashMap::new()),
+        })
+    }
+
+    fn data(&self) -> Result<&[u8]> {
+        if let Some(data) = self.data.get() {
+            return Ok(data);
+        }
+        let data = fs::read(&self.index.pack_path)
+            .with_context(|| format!("Failed to read pack {}", self.index.pack_path.display()))?;
+        Ok(self.data.get_or_init(|| data))
+    }
+
+    fn cached(&self, offset: u64) -> Option<(ObjectKind, Vec<u8>)> {
+        self.resolved.borrow().get(&offset).cloned()
+    }
+
+    fn remember(&self, offset: u64, kind: ObjectKind, payload: &[u8]) {
+        let mut resolved = self.resolved.borrow_mut();
+        if resolved.len() >= BASE_CACHE_LIMIT {
+            resolved.clear();
+        }
+        resolved.insert(offset, (kind, payload.to_vec()));
+    }
+}
+
+thread_local! {
+    // a pack is named after its checksum, so a path always holds the same content
+    static LOADED: RefCell<HashMap<PathBuf, Rc<LoadedPack>>> = RefCell::new(HashMap::new());
+}
+
+// Every pack of the repository, each one opened once per process.
+fn loaded_packs() -> Result<Vec<Rc<LoadedPack>>> {
+    let paths = list_packs()?;
+    LOADED.with(|loaded| {
+        let mut loaded = loaded.borrow_mut();
+        loaded.retain(|path, _| paths.contains(path));
+        paths
+            .iter()
+            .map(|path| {
+                if let Some(pack) = loaded.get(path) {
+                    return Ok(pack.clone());
+                }
+                let pack = Rc::new(LoadedPack::open(path)?);
+                loaded.insert(path.clone(), pack.clone());
+                Ok(pack)
+            })
+            .collect()
+    })
+}
+
+fn find_in(packs: &[Rc<LoadedPack>], hash: &[u8; 20]) -> Option<(Rc<LoadedPack>, u64)> {
+    packs
+        .iter()
+        .find_map(|pack| pack.index.find(hash).map(|offset| (pack.clone(), offset)))
+}
+
+pub fn contains(obj_hash: &str) -> bool {
+    let Ok(hash) = hash_to_bytes(obj_hash) else {
+        return false;
+    };
+    loaded_packs()
+        .map(|packs| find_in(&packs, &hash).is_some())
+        .unwrap_or(false)
+}
+
+/*
+ * Looks for the object in every pack. A delta chain is followed in the pack that
+ * holds it, then in the other packs, a base found in none of them is read as a
+ * loose object. Every entry rebuilt on the way is cached for the next lookups.
+ * */
+pub fn read_packed(obj_hash: &str) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+    let packs = loaded_packs()?;
+    let Some((mut pack, mut offset)) = find_in(&packs, &hash_to_bytes(obj_hash)?) else {
+        return Ok(None);
+    };
+    let max_chain: usize = packs.iter().map(|pack| pack.index.count).sum();
+    let mut chain = Vec::new();
+    let (kind, mut payload) = loop {
+        if let Some(resolved) = pack.cached(offset) {
+            break resolved;
+        }
+        let (base, delta) = match read_entry(pack.data()?, offset as usize)? {
+            PackEntry::Full(kind, payload) => {
+                pack.remember(offset, kind, &payload);
+                break (kind, payload);
+            }
+            PackEntry::Delta { base, delta } => (base, delta),
+        };
+        chain.push((pack.clone(), offset, delta));
+        if chain.len() > max_chain {
+            return Err(anyhow!("Delta chain of {} loops back on itself", obj_hash));
+        }
+        let base_hash = hash_to_bytes(&base)?;
+        if let Some(base_offset) = pack.index.find(&base_hash) {
+            offset = base_offset;
+        } else if let Some((base_pack, base_offset)) = find_in(&packs, &base_hash) {
+            (pack, offset) = (base_pack, base_offset);
+        } else {
+            break store::read_object(&base)?;
+        }
+    };
+    for (pack, offset, delta) in chain.iter().rev() {
+        payload = delta::apply_delta(&payload, delta)?;
+        pack.remember(*offset, kind, &payload);
+    }
+    Ok(Some((kind, payload)))
+}
+
+// Blob names found in the trees of the pack, used to put similar files next to each other.
+fn name_hints(objects: &[PackObject]) -> HashMap<String, String> {
+    let mut hints = HashMap::new();
+    for obj in objects.iter().filter(|obj| obj.kind == ObjectKind::Tree) {
+        let Ok(content) = std::str::from_utf8(&obj.payload) else {
+            continue;
+        };
+        for line in content.lines() {
+            if let Ok(entry) = TreeEntry::from_pretty_print(line) {
+                hints.entry(entry.hash).or_insert(entry.name);
+            }
+        }
+    }
+    hints
+}
+
+/*
+ * Objects of the same type are sorted by name and then by decreasing size, every object
+ * is compared against the previous `window` ones and stored as a delta when that is
+ * at most half of its size. Chains never get longer than `depth`.
+ * */
+fn choose_bases(
+    objects: &[PackObject],
+    window: usize,
+    depth: usize,
+) -> Vec<Option<(usize, Vec<u8>)>> {
+    let hints = name_hints(objects);
+    let mut order: Vec<usize> = (0..objects.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (obj_a, obj_b) = (&objects[a], &objects[b]);
+        kind_to_type(obj_a.kind)
+            .cmp(&kind_to_type(obj_b.kind))
+            .then_with(|| hints.get(&obj_a.hash).cmp(&hints.get(&obj_b.hash)))
+            .then_with(|| obj_b.payload.len().cmp(&obj_a.payload.len()))
+    });
+
+    let mut bases: Vec<Option<(usize, Vec<u8>)>> = (0..objects.len()).map(|_| None).collect();
+    let mut depths = vec![0usize; objects.len()];
+    for (pos, &current) in order.iter().enumerate() {
+        let target = &objects[current];
+        if target.payload.len() < 64 {
+            continue;
+        }
+        let mut best: Option<(usize, Vec<u8>)> = None;
+        for &candidate in order[pos.saturating_sub(window)..pos].iter().rev() {
+            let base = &objects[candidate];
+            if base.kind != target.kind || depths[candidate] >= depth {
+                continue;
+            }
+            let delta = delta::create_delta(&base.payload, &target.payload);
+            let limit = best
+                .as_ref()
+                .map(|(_, d)| d.len())
+                .unwrap_or(target.payload.len() / 2);
+            if delta.len() < limit {
+                best = Some((candidate, delta));
+            }
+        }
+        if let Some((base, delta)) = best {
+            depths[current] = depths[base] + 1;
+            bases[current] = Some((base, delta));
+        }
+    }
+    bases
+}
+
+// Writes a new pack with its index and returns the path of the pack.
+pub fn write_pack(objects: &[PackObject], window: usize, depth: usize) -> Result<PathBuf> {
+    let bases = choose_bases(objects, window, depth);
+
+    let mut pack = Vec::new();
+    pack.extend_from_slice(PACK_MAGIC);
+    pack.extend_from_slice(&VERSION.to_be_bytes());
+    pack.extend_from_slice(&(objects.len() as u32).to_be_bytes());
+
+    let mut offsets: Vec<([u8; 20], u64)> = Vec::with_capacity(objects.len());
+    for (i, obj) in objects.iter().enumerate() {
+        offsets.push((hash_to_bytes(&obj.hash)?, pack.len() as u64));
+        match &bases[i] {
+            Some((base, delta)) => {
+                pack.push(TYPE_REF_DELTA);
+                delta::write_varint(&mut pack, delta.len());
+                pack.extend_from_slice(&hash_to_bytes(&objects[*base].hash)?);
+                pack.extend_from_slice(&compression::compress(delta)?);
+            }
+            None => {
+                pack.push(kind_to_type(obj.kind));
+                delta::write_varint(&mut pack, obj.payload.len());
+                pack.extend_from_slice(&compression::compress(&obj.payload)?);
+            }
+        }
+    }
+    let checksum = calculate_hash(&pack);
+    let checksum_bytes = hash_to_bytes(&checksum)?;
+    pack.extend_from_slice(&checksum_bytes);
+
+    offsets.sort();
+    let mut idx = Vec::new();
+    idx.extend_from_slice(IDX_MAGIC);
+    idx.extend_from_slice(&VERSION.to_be_bytes());
+    idx.extend_from_slice(&(offsets.len() as u32).to_be_bytes());
+    for (hash, offset) in &offsets {
+        idx.extend_from_slice(hash);
+        idx.extend_from_slice(&offset.to_be_bytes());
+    }
+    idx.extend_from_slice(&checksum_bytes);
+
+    fs::create_dir_all(pack_dir())?;
+    let pack_path = pack_dir().join(format!("pack-{}.pack", checksum));
+    // the pack is written first, an idx without its pack would break every read
+    fs::write(&pack_path, &pack)?;
+    fs::write(pack_path.with_extension("idx"), &idx)?;
+    Ok(pack_path)
+}
+
+// Rebuilds an object from this pack alone, its whole delta chain has to be in it.
+fn read_from(pack: &LoadedPack, obj_hash: &str) -> Result<(ObjectKind, Vec<u8>)> {
+    let mut offset = pack
+        .index
+        .find(&hash_to_bytes(obj_hash)?)
+        .ok_or_else(|| anyhow!("Object {} is missing from the new pack", obj_hash))?;
+    let mut chain = Vec::new();
+    let (kind, mut payload) = loop {
+        if let Some(resolved) = pack.cached(offset) {
+            break resolved;
+        }
+        match read_entry(pack.data()?, offset as usize)? {
+            PackEntry::Full(kind, payload) => {
+                pack.remember(offset, kind, &payload);
+                break (kind, payload);
+            }
+            PackEntry::Delta { base, delta } => {
+                chain.push((offset, delta));
+                if chain.len() > pack.index.count {
+                    return Err(anyhow!("Delta chain of {} loops back on itself", obj_hash));
+                }
+                offset = pack.index.find(&hash_to_bytes(&base)?).ok_or_else(|| {
+                    anyhow!("Delta base {} of {} is not in the new pack", base, obj_hash)
+                })?;
+            }
+        }
+    };
+    for (offset, delta) in chain.iter().rev() {
+        payload = delta::apply_delta(&payload, delta)?;
+        pack.remember(*offset, kind, &payload);
+    }
+    Ok((kind, payload))
+}
+
+/*
+ * Makes sure every object can be rebuilt from the new pack before the originals
+ * are removed. The pack is read on its own, an object still found in an older
+ * pack or as a loose object must not hide a broken entry.
+ * */
+pub fn verify_objects(pack_path: &Path, objects: &[PackObject]) -> Result<()> {
+    let pack = LoadedPack::open(&pack_path.with_extension("idx"))?;
+    for obj in objects {
+        let (kind, payload) = read_from(&pack, &obj.hash)?;
+        if kind != obj.kind || store::hash_object(kind, &payload) != obj.hash {
+            return Err(anyhow!("Object {} was not packed correctly", obj.hash));
+        }
+    }
+    Ok(())
+}
+
+pub fn verify_checksum(pack_path: &Path) -> Result<()> {
+    let pack = fs::read(pack_path)?;
+    if pack.len() < 32 || &pack[0..4] != PACK_MAGIC {
+        return Err(anyhow!("Not a pack file"));
+    }
+    let (content, trailer) = pack.split_at(pack.len() - 20);
+    if calculate_hash(content) != bytes_to_hash(trailer) {
+        return Err(anyhow!("Pack checksum mismatch"));
+    }
+    let idx = PackIndex::open(&pack_path.with_extension("idx"))?;
+    if idx.data[idx.data.len() - 20..] != *trailer {
+        return Err(anyhow!("Pack index does not belong to this pack"));
+    }
+    Ok(())
+}
+
+pub fn delta_count(pack_path: &Path) -> Result<usize> {
+    let idx = PackIndex::open(&pack_path.with_extension("idx"))?;
+    let pack = fs::read(pack_path)?;
+    let mut count = 0;
+    for hash in idx.hashes() {
+        let offset = idx.find(&hash_to_bytes(&hash)?).unwrap_or_default();
+        if pack.get(offset as usize) == Some(&TYPE_REF_DELTA) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
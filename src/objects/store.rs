@@ -0,0 +1,220 @@
+use crate::objects::pack;
+use crate::others::compression;
+use crate::others::hash_function::calculate_hash;
+use anyhow::{anyhow, Context, Result};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/*
+ * Every object is stored as "<type> <size>\0<payload>", compressed with zlib,
+ * under .vcs/objects/<first two chars of the hash>/<remaining chars>.
+ * The hash is always computed over the header and the payload, so a blob and
+ * a tree with the same bytes get different ids.
+ * Objects that were repacked live in .vcs/objects/pack instead, reads look
+ * there when no loose object exists.
+ * */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+impl ObjectKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectKind::Blob => "blob",
+            ObjectKind::Tree => "tree",
+            ObjectKind::Commit => "commit",
+            ObjectKind::Tag => "tag",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "blob" => Ok(ObjectKind::Blob),
+            "tree" => Ok(ObjectKind::Tree),
+            "commit" => Ok(ObjectKind::Commit),
+            "tag" => Ok(ObjectKind::Tag),
+            _ => Err(anyhow!("Unknown object type: {}", name)),
+        }
+    }
+}
+
+impl fmt::Display for ObjectKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+pub fn objects_dir() -> PathBuf {
+    Path::new(".vcs").join("objects")
+}
+
+pub fn object_path(obj_hash: &str) -> Result<PathBuf> {
+    if obj_hash.len() != 40 || !obj_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!("Invalid object hash: {}", obj_hash));
+    }
+    Ok(objects_dir().join(&obj_hash[0..2]).join(&obj_hash[2..]))
+}
+
+pub fn encode(kind: ObjectKind, payload: &[u8]) -> Vec<u8> {
+    let mut content = format!("{} {}\0", kind, payload.len()).into_bytes();
+    content.extend_from_slice(payload);
+    content
+}
+
+pub fn decode(raw: &[u8]) -> Result<(ObjectKind, Vec<u8>)> {
+    let nul = raw
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| anyhow!("Object header is missing"))?;
+    let header = std::str::from_utf8(&raw[..nul]).context("Object header is not valid UTF-8")?;
+    let (kind, size) = header
+        .split_once(' ')
+        .ok_or_else(|| anyhow!("Malformed object header: {}", header))?;
+    let kind = ObjectKind::parse(kind)?;
+    let size: usize = size
+        .parse()
+        .with_context(|| format!("Malformed object size: {}", size))?;
+    let payload = &raw[nul + 1..];
+    if payload.len() != size {
+        return Err(anyhow!(
+            "Object size mismatch: header says {} bytes, found {}",
+            size,
+            payload.len()
+        ));
+    }
+    Ok((kind, payload.to_vec()))
+}
+
+pub fn hash_object(kind: ObjectKind, payload: &[u8]) -> String {
+    calculate_hash(&encode(kind, payload))
+}
+
+pub fn object_exists(obj_hash: &str) -> bool {
+    object_path(obj_hash).map(|p| p.exists()).unwrap_or(false) || pack::contains(obj_hash)
+}
+
+// Hashes of every loose object, packed objects are not included.
+pub fn loose_objects() -> Result<Vec<String>> {
+    let mut hashes = Vec::new();
+    if !objects_dir().exists() {
+        return Err(anyhow!("The repository is not initialized"));
+    }
+    for entry in fs::read_dir(objects_dir())? {
+        let entry = entry?;
+        let prefix = entry.file_name().to_string_lossy().to_string();
+        if prefix.len() != 2 || !entry.path().is_dir() {
+            continue;
+        }
+        for object in fs::read_dir(entry.path())? {
+            let hash = format!("{}{}", prefix, object?.file_name().to_string_lossy());
+            if object_path(&hash).is_ok() {
+                hashes.push(hash);
+            }
+        }
+    }
+    hashes.sort();
+    Ok(hashes)
+}
+
+// Every object, loose or packed, whose hash starts with the given hex prefix.
+pub fn objects_with_prefix(prefix: &str) -> Result<Vec<String>> {
+    let prefix = prefix.to_ascii_lowercase();
+    let mut hashes: Vec<String> = loose_objects()?
+        .into_iter()
+        .filter(|hash| hash.starts_with(&prefix))
+        .collect();
+    for idx_path in pack::list_packs()? {
+        for hash in pack::PackIndex::open(&idx_path)?.hashes() {
+            if hash.starts_with(&prefix) {
+                hashes.push(hash);
+            }
+        }
+    }
+    hashes.sort();
+    hashes.dedup();
+    Ok(hashes)
+}
+
+pub fn write_object(kind: ObjectKind, payload: &[u8]) -> Result<String> {
+    let id = hash_object(kind, payload);
+    // objects are immutable, an existing object already holds the same content
+    if object_exists(&id) {
+        return Ok(id);
+    }
+    write_loose_object(kind, payload)
+}
+
+// Writes the object as a loose file even when a pack already contains it.
+pub fn write_loose_object(kind: ObjectKind, payload: &[u8]) -> Result<String> {
+    if !objects_dir().exists() {
+        return Err(anyhow!("The repository is not initialized"));
+    }
+    let content = encode(kind, payload);
+    let id = calculate_hash(&content);
+    let file_path = object_path(&id)?;
+    if file_path.exists() {
+        return Ok(id);
+    }
+    if let Some(subfolder) = file_path.parent() {
+        fs::create_dir_all(subfolder)?;
+    }
+    let compressed_content = compression::compress(&content)?;
+    let mut file = fs::File::create(file_path)?;
+    file.write_all(&compressed_content)?;
+    Ok(id)
+}
+
+pub fn remove_loose_object(obj_hash: &str) -> Result<()> {
+    let path = object_path(obj_hash)?;
+    fs::remove_file(&path).with_context(|| format!("Failed to remove object {}", obj_hash))?;
+    if let Some(subfolder) = path.parent() {
+        if fs::read_dir(subfolder)?.next().is_none() {
+            fs::remove_dir(subfolder)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn read_object(obj_hash: &str) -> Result<(ObjectKind, Vec<u8>)> {
+    let path = object_path(obj_hash)?;
+    if !path.exists() {
+        return pack::read_packed(obj_hash)
+            .with_context(|| format!("Corrupt packed object {}", obj_hash))?
+            .ok_or_else(|| anyhow!("Object {} does not exist", obj_hash));
+    }
+    let decompressed = read_loose_raw(obj_hash)?;
+    decode(&decompressed).with_context(|| format!("Corrupt object {}", obj_hash))
+}
+
+// The decompressed content of a loose object, header included.
+pub fn read_loose_raw(obj_hash: &str) -> Result<Vec<u8>> {
+    let path = object_path(obj_hash)?;
+    let file_content =
+        fs::read(&path).with_context(|| format!("Object {} does not exist", obj_hash))?;
+    compression::decompress(&file_content)
+}
+
+pub fn read_object_of(obj_hash: &str, expected: ObjectKind) -> Result<Vec<u8>> {
+    let (kind, payload) = read_object(obj_hash)?;
+    if kind != expected {
+        return Err(anyhow!(
+            "Object {} is a {}, expected a {}",
+            obj_hash,
+            kind,
+            expected
+        ));
+    }
+    Ok(payload)
+}
+
+pub fn read_text(obj_hash: &str, expected: ObjectKind) -> Result<String> {
+    let payload = read_object_of(obj_hash, expected)?;
+    String::from_utf8(payload).with_context(|| format!("Object {} is not valid UTF-8", obj_hash))
+}
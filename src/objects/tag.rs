@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Result};
+
+use crate::objects::commit::Signature;
+use crate::objects::store::{self, ObjectKind};
+
+/*
+ * An annotated tag, serialized like git:
+ *     object <hash of the tagged object>
+ *     type commit
+ *     tag v1.0
+ *     tagger Jane Doe <jane@example.com> 1700000000 +0200
+ *
+ *     <message>
+ * Lightweight tags are plain refs under refs/tags and have no object.
+ * */
+pub struct Tag {
+    pub id: String,
+    pub content: TagContent,
+}
+
+pub struct TagContent {
+    pub object: String,
+    pub kind: ObjectKind, // type of the tagged object
+    pub name: String,
+    pub tagger: Signature,
+    pub message: String,
+}
+
+impl TagContent {
+    pub fn pretty_print(&self) -> String {
+        format!(
+            "object {}\ntype {}\ntag {}\ntagger {}\n\n{}\n",
+            self.object,
+            self.kind,
+            self.name,
+            self.tagger.pretty_print(),
+            self.message
+        )
+    }
+    pub fn from_pretty_print(content: &str) -> Result<Self> {
+        let (header, message) = content
+            .split_once("\n\n")
+            .ok_or_else(|| anyhow!("Missing message in tag content."))?;
+        let (mut object, mut kind, mut name, mut tagger) = (None, None, None, None);
+        for line in header.lines() {
+            if let Some(stripped) = line.strip_prefix("object ") {
+                object = Some(stripped.to_string());
+            } else if let Some(stripped) = line.strip_prefix("type ") {
+                kind = Some(ObjectKind::parse(stripped)?);
+            } else if let Some(stripped) = line.strip_prefix("tag ") {
+                name = Some(stripped.to_string());
+            } else if let Some(stripped) = line.strip_prefix("tagger ") {
+                tagger = Some(Signature::from_pretty_print(stripped)?);
+            } else {
+                return Err(anyhow!("Unexpected line in tag: {}", line));
+            }
+        }
+        Ok(Self {
+            object: object.ok_or_else(|| anyhow!("Missing object in tag content."))?,
+            kind: kind.ok_or_else(|| anyhow!("Missing type in tag content."))?,
+            name: name.ok_or_else(|| anyhow!("Missing name in tag content."))?,
+            tagger: tagger.ok_or_else(|| anyhow!("Missing tagger in tag content."))?,
+            message: message.trim_end_matches('\n').to_string(),
+        })
+    }
+}
+
+impl Tag {
+    pub fn new(
+        object: String,
+        kind: ObjectKind,
+        name: String,
+        tagger: Signature,
+        message: String,
+    ) -> Self {
+        let content = TagContent {
+            object,
+            kind,
+            name,
+            tagger,
+            message,
+        };
+        let id = store::hash_object(ObjectKind::Tag, content.pretty_print().as_bytes());
+        Self { id, content }
+    }
+    pub fn create_tag(&self) -> Result<()> {
+        store::write_object(ObjectKind::Tag, self.content.pretty_print().as_bytes())?;
+        Ok(())
+    }
+    pub fn read(tag_hash: &str) -> Result<TagContent> {
+        let content = store::read_text(tag_hash, ObjectKind::Tag)?;
+        TagContent::from_pretty_print(&content)
+    }
+}
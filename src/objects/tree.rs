@@ -1,118 +1,200 @@
-use crate::commands::dec_object::dec_obj;
-use crate::others::compression;
-use crate::others::hash_function::calculate_hash;
+use crate::objects::store::{self, ObjectKind};
 use crate::others::index;
-use anyhow::Result;
-use std::fs::read_to_string;
-use std::fs::{self};
-use std::io::Write;
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+
+pub const MODE_FILE: u32 = 0o100644;
+pub const MODE_EXECUTABLE: u32 = 0o100755;
+pub const MODE_TREE: u32 = 0o40000;
+
+/*
+ * A tree holds one line per entry, sorted by name:
+ *     <mode in octal> <type> <hash>\t<name>
+ * Only the mode, the name and the hash of the child are recorded, so two trees
+ * are equal exactly when the content they describe is equal.
+ * */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub mode: u32,
+    pub kind: ObjectKind,
+    pub hash: String,
+    pub name: String,
+}
+
+impl TreeEntry {
+    pub fn pretty_print(&self) -> String {
+        format!("{:o} {} {}\t{}", self.mode, self.kind, self.hash, self.name)
+    }
+    pub fn from_pretty_print(line: &str) -> Result<Self> {
+        let (meta, name) = line
+            .split_once('\t')
+            .ok_or_else(|| anyhow!("Malformed tree entry: {}", line))?;
+        let parts: Vec<&str> = meta.split(' ').collect();
+        if parts.len() != 3 || name.is_empty() {
+            return Err(anyhow!("Malformed tree entry: {}", line));
+        }
+        Ok(Self {
+            mode: u32::from_str_radix(parts[0], 8).context("Failed to parse tree entry mode")?,
+            kind: ObjectKind::parse(parts[1])?,
+            hash: parts[2].to_string(),
+            name: name.to_string(),
+        })
+    }
+}
+
+// Only the executable bit of a file is worth recording in a tree.
+pub fn file_mode(permissions: u32) -> u32 {
+    if permissions & 0o111 != 0 {
+        MODE_EXECUTABLE
+    } else {
+        MODE_FILE
+    }
+}
+
 pub struct Tree {
     pub id: String,
+    pub entries: Vec<TreeEntry>,
+    subtrees: Vec<Tree>,
 }
 
-pub fn read_file_content(file_name: &str) -> Result<Vec<u8>> {
-    let content = read_to_string(file_name)?;
-    Ok(content.into_bytes())
+enum Node {
+    File { mode: u32, hash: String },
+    Dir(BTreeMap<String, Node>),
 }
 
 impl Tree {
     pub fn new() -> Result<Self> {
-        let index_content = read_file_content(".vcs/index")?;
-        let header = format!("tree{}\0", index_content.len());
-        let mut content = header.into_bytes();
-        content.extend_from_slice(&index_content);
-        let id = calculate_hash(&content);
-        Ok(Tree { id })
+        let index = index::Index::load()?;
+        Self::new_tree_from_index(index)
     }
-    pub fn create_tree(&self) -> Result<()> {
-        let vcs_objects_path: PathBuf = Path::new(".vcs").join("objects");
-        if !vcs_objects_path.exists() {
-            return Err(anyhow::anyhow!("The repository is not initialized"));
-        }
 
-        let subfolder = vcs_objects_path.join(&self.id[0..2]);
-
-        if !subfolder.exists() {
-            fs::create_dir_all(&subfolder)?;
+    // Writes this tree and every subtree it is built from.
+    pub fn create_tree(&self) -> Result<()> {
+        for subtree in &self.subtrees {
+            subtree.create_tree()?;
         }
-
-        let file_path = subfolder.join(&self.id[2..]);
-        let index_content = read_file_content(".vcs/index")?;
-        let header = format!("tree{}\0", index_content.len());
-        let mut content = header.into_bytes();
-        content.extend_from_slice(&index_content);
-
-        let compressed_content = compression::compress(&index_content)?;
-
-        let mut file = fs::File::create(file_path)?;
-        file.write_all(&compressed_content)?;
-
+        store::write_object(ObjectKind::Tree, Self::serialize(&self.entries).as_bytes())?;
         Ok(())
     }
 
-    pub fn merge_tree(tree1: &str, tree2: &str) -> Result<index::Index> {
-        let tree1_content = dec_obj(tree1)?;
-        let tree2_content = dec_obj(tree2)?;
-
-        let mut merged_index = index::Index::new();
-
-        // Parse tree1 into the merged index
-        for line in tree1_content.lines() {
-            let obj_info = index::ObjectInfo::from_pretty_print(line)?;
-            merged_index.add_object(obj_info);
+    pub fn new_tree_from_index(index: index::Index) -> Result<Self> {
+        let mut root = BTreeMap::new();
+        for obj in index.obj {
+            if obj.stage != 0 {
+                return Err(anyhow!(
+                    "Cannot write a tree, '{}' has unresolved conflicts",
+                    obj.path.display()
+                ));
+            }
+            let components: Vec<String> = obj
+                .path
+                .components()
+                .filter_map(|c| match c {
+                    std::path::Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .collect();
+            if components.is_empty() {
+                return Err(anyhow!("Invalid path in index: {}", obj.path.display()));
+            }
+            Self::insert(&mut root, &components, file_mode(obj.permissions), obj.hash)?;
         }
+        Ok(Self::from_nodes(root))
+    }
 
-        // Parse tree2 and overwrite or add to the merged index
-        for line in tree2_content.lines() {
-            let obj_info = index::ObjectInfo::from_pretty_print(line)?;
-
-            // If the object already exists, replace it
-            merged_index.obj.retain(|obj| obj.path != obj_info.path);
-            merged_index.add_object(obj_info);
+    fn insert(
+        dir: &mut BTreeMap<String, Node>,
+        components: &[String],
+        mode: u32,
+        hash: String,
+    ) -> Result<()> {
+        let name = components[0].clone();
+        if components.len() == 1 {
+            if let Some(Node::Dir(_)) = dir.get(&name) {
+                return Err(anyhow!("'{}' is both a file and a directory", name));
+            }
+            dir.insert(name, Node::File { mode, hash });
+            return Ok(());
+        }
+        let child = dir
+            .entry(name.clone())
+            .or_insert_with(|| Node::Dir(BTreeMap::new()));
+        match child {
+            Node::Dir(children) => Self::insert(children, &components[1..], mode, hash),
+            Node::File { .. } => Err(anyhow!("'{}' is both a file and a directory", name)),
         }
-
-        Ok(merged_index)
     }
 
-    pub fn new_tree_from_index(index: index::Index) -> Result<Self> {
-        let index_content = Self::get_index_content(index)?;
-        let header = format!("tree{}\0", index_content.len());
-        let mut content = header.into_bytes();
-        content.extend_from_slice(&index_content.into_bytes());
-        let id = calculate_hash(&content);
-        Ok(Tree { id })
+    fn from_nodes(nodes: BTreeMap<String, Node>) -> Self {
+        let mut entries = Vec::new();
+        let mut subtrees = Vec::new();
+        for (name, node) in nodes {
+            match node {
+                Node::File { mode, hash } => entries.push(TreeEntry {
+                    mode,
+                    kind: ObjectKind::Blob,
+                    hash,
+                    name,
+                }),
+                Node::Dir(children) => {
+                    let subtree = Self::from_nodes(children);
+                    entries.push(TreeEntry {
+                        mode: MODE_TREE,
+                        kind: ObjectKind::Tree,
+                        hash: subtree.id.clone(),
+                        name,
+                    });
+                    subtrees.push(subtree);
+                }
+            }
+        }
+        let id = store::hash_object(ObjectKind::Tree, Self::serialize(&entries).as_bytes());
+        Tree {
+            id,
+            entries,
+            subtrees,
+        }
     }
 
-    pub fn get_index_content(index: index::Index) -> Result<String> {
-        let mut index_content = String::new();
-        for obj in index.obj {
-            let obj_content = index::ObjectInfo::pretty_print(&obj);
-            index_content.push_str(&obj_content);
-            index_content.push('\n');
+    fn serialize(entries: &[TreeEntry]) -> String {
+        let mut content = String::new();
+        for entry in entries {
+            content.push_str(&entry.pretty_print());
+            content.push('\n');
         }
-        Ok(index_content)
+        content
     }
 
-    pub fn create_tree_from_index(&self, index: index::Index) -> Result<()> {
-        let vcs_objects_path: PathBuf = Path::new(".vcs").join("objects");
-        if !vcs_objects_path.exists() {
-            return Err(anyhow::anyhow!("The repository is not initialized"));
-        }
+    pub fn read(tree_hash: &str) -> Result<Vec<TreeEntry>> {
+        let content = store::read_text(tree_hash, ObjectKind::Tree)?;
+        content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(TreeEntry::from_pretty_print)
+            .collect()
+    }
 
-        let subfolder = vcs_objects_path.join(&self.id[0..2]);
+    // Walks the tree recursively and returns every blob with its full path.
+    pub fn flatten(tree_hash: &str) -> Result<index::Index> {
+        let mut files = index::Index::new();
+        Self::collect_files(tree_hash, Path::new(""), &mut files)?;
+        Ok(files)
+    }
 
-        if !subfolder.exists() {
-            fs::create_dir_all(&subfolder)?;
+    fn collect_files(tree_hash: &str, prefix: &Path, files: &mut index::Index) -> Result<()> {
+        for entry in Self::read(tree_hash)? {
+            let path: PathBuf = prefix.join(&entry.name);
+            match entry.kind {
+                ObjectKind::Tree => Self::collect_files(&entry.hash, &path, files)?,
+                ObjectKind::Blob => files.add_object(index::ObjectInfo::from_tree_entry(
+                    &path,
+                    &entry.hash,
+                    entry.mode,
+                )),
+                other => return Err(anyhow!("Unexpected {} in tree {}", other, tree_hash)),
+            }
         }
-        let index_content = Self::get_index_content(index)?;
-
-        let compressed_content = compression::compress(&index_content.into_bytes())?;
-
-        let file_path = subfolder.join(&self.id[2..]);
-        let mut file = fs::File::create(file_path)?;
-        file.write_all(&compressed_content)?;
-
         Ok(())
     }
 }
@@ -1,4 +1,11 @@
+pub mod config;
+pub mod diff3;
 pub mod index;
+pub mod line_diff;
+pub mod reachability;
+pub mod reflog;
+pub mod refs;
+pub mod revision;
 
 pub mod compression {
     use anyhow::{Context, Result};
@@ -35,14 +42,16 @@ pub mod hash_function {
 }
 
 pub mod file_altering {
-    use crate::commands::dec_object::dec_obj;
     use crate::commands::status::parse_gitignore;
-    use crate::objects::commit;
-    use crate::others::index;
+    use crate::objects::commit::Commit;
+    use crate::objects::store::{self, ObjectKind};
+    use crate::objects::tree::Tree;
+    use crate::others::{index, reflog, refs};
     use anyhow::{Context, Result};
     use glob::Pattern;
     use std::fs;
     use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::PermissionsExt;
     use std::path::Path;
     pub fn delete_nth_line(line_number: usize, path: &str) -> Result<()> {
         let file_path = Path::new(path);
@@ -94,6 +103,10 @@ pub mod file_altering {
             let entry = entry.context("Failed to read directory entry")?;
             let path = entry.path();
 
+            if entry.file_name() == ".vcs" {
+                continue;
+            }
+
             if let Ok(relative_path) = path.strip_prefix(".") {
                 let relative_str = relative_path.to_string_lossy();
                 if ignore_patt
@@ -120,21 +133,49 @@ pub mod file_altering {
         let head_content = fs::read_to_string(".vcs/HEAD")?;
         Ok(head_content)
     }
+    // The commit HEAD points at, None on a branch without commits.
+    pub fn get_head_commit() -> Result<Option<String>> {
+        let head = get_curent_branch()?.trim().to_string();
+        let hash = if head.starts_with("refs/") {
+            let ref_path = Path::new(".vcs").join(&head);
+            if ref_path.exists() {
+                fs::read_to_string(ref_path)?.trim().to_string()
+            } else {
+                String::new()
+            }
+        } else {
+            head
+        };
+        Ok(if hash.is_empty() { None } else { Some(hash) })
+    }
+
+    // The branch HEAD is on, None when HEAD is detached at a commit.
+    pub fn get_head_branch() -> Result<Option<String>> {
+        let head = get_curent_branch()?.trim().to_string();
+        Ok(head.strip_prefix("refs/heads/").map(str::to_string))
+    }
+
+    // Moves the current branch to the commit, or HEAD itself when it is detached.
+    pub fn update_head(commit_hash: &str, reason: &str) -> Result<()> {
+        let head = get_curent_branch()?.trim().to_string();
+        if head.starts_with("refs/") {
+            return refs::update_ref(&head, commit_hash, reason);
+        }
+        let head_path = Path::new(".vcs").join("HEAD");
+        fs::write(&head_path, commit_hash).context("Failed to update HEAD")?;
+        reflog::append("HEAD", Some(&head), commit_hash, reason)
+    }
+
     pub fn get_files_from_tree(tree_hash: &str) -> Result<(index::Index, Vec<String>)> {
-        let tree_content = dec_obj(tree_hash)?;
+        let tree_files = Tree::flatten(tree_hash)?;
         let mut index_tree = index::Index::new();
         let mut deleted_files = Vec::new();
-        for line in tree_content.lines() {
-            // Parse the object from the tree content
-            let parsed_object = index::ObjectInfo::from_pretty_print(line)?;
-
-            if parsed_object.obj_type == "blob" {
-                // Safely create the ObjectInfo, handling errors
-                match index::ObjectInfo::new("blob", &parsed_object.path, &parsed_object.hash) {
-                    Ok(obj_info) => index_tree.add_object(obj_info),
-                    Err(_) => {
-                        deleted_files.push(parsed_object.path.to_str().unwrap().to_string());
-                    }
+        for parsed_object in tree_files.obj {
+            // Safely create the ObjectInfo, handling errors
+            match index::ObjectInfo::new("blob", &parsed_object.path, &parsed_object.hash) {
+                Ok(obj_info) => index_tree.add_object(obj_info),
+                Err(_) => {
+                    deleted_files.push(parsed_object.path.to_str().unwrap().to_string());
                 }
             }
         }
@@ -149,7 +190,7 @@ pub mod file_altering {
         for file_path in filenames.lines() {
             if !file_path.starts_with(".vcs") {
                 let content = fs::read(file_path)?;
-                let file_hash = crate::others::hash_function::calculate_hash(&content);
+                let file_hash = store::hash_object(ObjectKind::Blob, &content);
                 let path = Path::new(file_path);
                 //let path = Path::new(&file_path[2..]);
                 let obj_info = index::ObjectInfo::new("blob", path, &file_hash);
@@ -159,39 +200,18 @@ pub mod file_altering {
         Ok(working_index)
     }
     pub fn get_tree_from_commit(commit_hash: &str) -> Result<String> {
-        let commit_content = dec_obj(commit_hash)?;
-        let commit_obj = commit::CommitContent::from_pretty_print(&commit_content)?;
+        let commit_obj = Commit::read(commit_hash)?;
         Ok(commit_obj.tree)
     }
     pub fn get_current_tree() -> Result<String> {
-        let mut current_branch_path = String::from(".vcs/");
-        current_branch_path.push_str(&get_curent_branch()?);
-        let current_commit_hash = fs::read_to_string(&current_branch_path);
-        let mut current_commit_hash1 = String::new();
-
-        match current_commit_hash {
-            Ok(ans) => {
-                if ans.is_empty() {
-                } else {
-                    current_commit_hash1.push_str(&ans);
-                }
-            }
-            Err(e) => {
-                return Err(e.into());
-            }
-        }
-        if !current_commit_hash1.is_empty() {
-            let current_pretty_commit = dec_obj(&current_commit_hash1)?;
-            let current_commit_content_obj =
-                commit::CommitContent::from_pretty_print(&current_pretty_commit)?;
-            Ok(current_commit_content_obj.tree)
-        } else {
-            Err(anyhow::anyhow!("err"))
+        match get_head_commit()? {
+            Some(commit_hash) => get_tree_from_commit(&commit_hash),
+            None => Err(anyhow::anyhow!("err")),
         }
     }
 
-    pub fn get_file_content(file: &str) -> Result<String> {
-        let content = fs::read_to_string(file)?;
+    pub fn get_file_bytes(file: &str) -> Result<Vec<u8>> {
+        let content = fs::read(file).with_context(|| format!("Failed to read file: {}", file))?;
         Ok(content)
     }
     /*pub fn get_current_commit() -> Result<String> {
@@ -201,30 +221,46 @@ pub mod file_altering {
         Ok(current_commit_hash)
     }*/
     pub fn get_commit_parent(commit_hash: &str) -> Result<Vec<String>> {
-        let commit_content = dec_obj(commit_hash)?;
-        let commit_obj = commit::CommitContent::from_pretty_print(&commit_content)?;
+        let commit_obj = Commit::read(commit_hash)?;
         /*if commit_obj.parents.is_empty() {
             return Err(anyhow::anyhow!("There is only one commit"));
         }*/
         Ok(commit_obj.parents.clone())
     }
 
-    pub fn get_commit_from_branch(branch: &str) -> Result<String> {
-        let path = Path::new(".vcs").join("refs").join("heads/");
-        let mut branch_path = path.to_str().unwrap().to_string();
-        branch_path.push_str(branch);
-        let content = fs::read_to_string(branch_path)?;
-        Ok(content)
+    pub fn build_index_from_tree(tree_hash: &str) -> Result<index::Index> {
+        Tree::flatten(tree_hash)
     }
 
-    pub fn build_index_from_tree(tree_hash: &str) -> Result<index::Index> {
-        let content = dec_obj(tree_hash)?;
+    // Writes a tracked file, creating its directories, with the mode recorded in the tree.
+    pub fn write_working_file(path: &Path, content: &[u8], mode: u32) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))?;
+        Ok(())
+    }
 
-        let mut index = index::Index::new();
-        for line in content.lines() {
-            let obj_info = index::ObjectInfo::from_pretty_print(line)?;
-            index.add_object(obj_info);
+    // Removes a tracked file and the directories it leaves empty.
+    pub fn remove_working_file(path: &Path) -> Result<()> {
+        if path.is_file() {
+            fs::remove_file(path)?;
         }
-        Ok(index)
+        let mut dir = path.parent();
+        while let Some(parent) = dir {
+            // a parent already gone was pruned along with an earlier file
+            if parent.as_os_str().is_empty()
+                || !parent.exists()
+                || fs::read_dir(parent)?.next().is_some()
+            {
+                break;
+            }
+            fs::remove_dir(parent)?;
+            dir = parent.parent();
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,100 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/*
+ * .vcs/config uses the same layout as git:
+ *     [user]
+ *         name = Jane Doe
+ *         email = jane@example.com
+ * Keys are addressed as "<section>.<name>".
+ * */
+
+pub fn config_path() -> PathBuf {
+    Path::new(".vcs").join("config")
+}
+
+fn split_key(key: &str) -> Result<(&str, &str)> {
+    match key.split_once('.') {
+        Some((section, name)) if !section.is_empty() && !name.is_empty() => Ok((section, name)),
+        _ => Err(anyhow!(
+            "Invalid config key '{}', expected <section>.<name>",
+            key
+        )),
+    }
+}
+
+fn load() -> Result<BTreeMap<String, BTreeMap<String, String>>> {
+    let mut sections: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    let path = config_path();
+    if !path.exists() {
+        return Ok(sections);
+    }
+    let content = fs::read_to_string(&path).context("Failed to read the config file")?;
+    let mut current = String::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = section.trim().to_string();
+            continue;
+        }
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Malformed config line {}: {}", number + 1, line))?;
+        if current.is_empty() {
+            return Err(anyhow!(
+                "Config line {} is outside of a section",
+                number + 1
+            ));
+        }
+        sections
+            .entry(current.clone())
+            .or_default()
+            .insert(name.trim().to_string(), value.trim().to_string());
+    }
+    Ok(sections)
+}
+
+pub fn get(key: &str) -> Result<Option<String>> {
+    let (section, name) = split_key(key)?;
+    Ok(load()?
+        .get(section)
+        .and_then(|values| values.get(name))
+        .cloned())
+}
+
+pub fn set(key: &str, value: &str) -> Result<()> {
+    let (section, name) = split_key(key)?;
+    if !Path::new(".vcs").exists() {
+        return Err(anyhow!("The repository is not initialized"));
+    }
+    let mut sections = load()?;
+    sections
+        .entry(section.to_string())
+        .or_default()
+        .insert(name.to_string(), value.to_string());
+
+    let mut content = String::new();
+    for (section, values) in &sections {
+        content.push_str(&format!("[{}]\n", section));
+        for (name, value) in values {
+            content.push_str(&format!("\t{} = {}\n", name, value));
+        }
+    }
+    fs::write(config_path(), content).context("Failed to write the config file")?;
+    Ok(())
+}
+
+pub fn list() -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    for (section, values) in load()? {
+        for (name, value) in values {
+            entries.push((format!("{}.{}", section, name), value));
+        }
+    }
+    Ok(entries)
+}
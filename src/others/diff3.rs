@@ -0,0 +1,177 @@
+use crate::others::line_diff::{self, DiffAlgorithm, Edit};
+
+/*
+ * Line based three-way merge. Both sides are diffed against the base, the base
+ * lines that survive unchanged on both sides split the files into stable
+ * chunks and the changed regions between them:
+ *     only one side changed the region    that side wins
+ *     both sides made the same change     taken once
+ *     both sides changed it differently   conflict, written with markers
+ *         <<<<<<< ours
+ *         ...
+ *         =======
+ *         ...
+ *         >>>>>>> theirs
+ * With favor_theirs set, conflicting regions take their side without markers.
+ * */
+
+pub struct MergedText {
+    pub content: String,
+    pub conflicts: usize,
+}
+
+// For every base line, the line of the other side it was kept as.
+fn matches(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matched = vec![None; base.len()];
+    let (mut i, mut j) = (0, 0);
+    for edit in line_diff::diff(base, other, DiffAlgorithm::Histogram) {
+        match edit {
+            Edit::Equal => {
+                matched[i] = Some(j);
+                i += 1;
+                j += 1;
+            }
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+    matched
+}
+
+fn push_lines(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+}
+
+// Markers must start on their own line even when the side lacks a final newline.
+fn push_side(out: &mut String, lines: &[&str]) {
+    push_lines(out, lines);
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+pub fn merge(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    ours_label: &str,
+    theirs_label: &str,
+    favor_theirs: bool,
+) -> MergedText {
+    let base: Vec<&str> = base.split_inclusive('\n').collect();
+    let ours: Vec<&str> = ours.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let in_ours = matches(&base, &ours);
+    let in_theirs = matches(&base, &theirs);
+
+    let mut content = String::new();
+    let mut conflicts = 0;
+    let (mut o, mut a, mut b) = (0, 0, 0);
+    loop {
+        // stable lines, unchanged on both sides
+        while o < base.len() && in_ours[o] == Some(a) && in_theirs[o] == Some(b) {
+            content.push_str(base[o]);
+            o += 1;
+            a += 1;
+            b += 1;
+        }
+        if o == base.len() && a == ours.len() && b == theirs.len() {
+            break;
+        }
+
+        // the changed region ends at the next base line both sides kept
+        let next = (o..base.len()).find(|&k| in_ours[k].is_some() && in_theirs[k].is_some());
+        let (o_end, a_end, b_end) = match next {
+            Some(k) => (k, in_ours[k].unwrap(), in_theirs[k].unwrap()),
+            None => (base.len(), ours.len(), theirs.len()),
+        };
+        let base_chunk = &base[o..o_end];
+        let ours_chunk = &ours[a..a_end];
+        let theirs_chunk = &theirs[b..b_end];
+
+        if ours_chunk == base_chunk || ours_chunk == theirs_chunk {
+            push_lines(&mut content, theirs_chunk);
+        } else if theirs_chunk == base_chunk {
+            push_lines(&mut content, ours_chunk);
+        } else if favor_theirs {
+            push_lines(&mut content, theirs_chunk);
+        } else {
+            conflicts += 1;
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str(&format!("<<<<<<< {}\n", ours_label));
+            push_side(&mut content, ours_chunk);
+            content.push_str("=======\n");
+            push_side(&mut content, theirs_chunk);
+            content.push_str(&format!(">>>>>>> {}\n", theirs_label));
+        }
+        o = o_end;
+        a = a_end;
+        b = b_end;
+    }
+
+    MergedText { content, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "one\ntwo\nthree\nfour\nfive\n";
+
+    #[test]
+    fn clean_merge() {
+        let ours = "ONE\ntwo\nthree\nfour\nfive\n";
+        let theirs = "one\ntwo\nthree\nfour\nFIVE\n";
+        let merged = merge(BASE, ours, theirs, "ours", "theirs", false);
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(merged.content, "ONE\ntwo\nthree\nfour\nFIVE\n");
+    }
+
+    #[test]
+    fn same_change_on_both_sides() {
+        let changed = "one\ntwo\nTHREE\nand a half\nfour\nfive\n";
+        let merged = merge(BASE, changed, changed, "ours", "theirs", false);
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(merged.content, changed);
+    }
+
+    #[test]
+    fn conflict_is_written_with_markers() {
+        let ours = "one\ntwo\nmine\nfour\nfive\n";
+        let theirs = "one\ntwo\nyours\nfour\nfive\n";
+        let merged = merge(BASE, ours, theirs, "HEAD", "feature", false);
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(
+            merged.content,
+            "one\ntwo\n<<<<<<< HEAD\nmine\n=======\nyours\n>>>>>>> feature\nfour\nfive\n"
+        );
+    }
+
+    #[test]
+    fn favor_theirs_takes_their_side() {
+        let ours = "one\ntwo\nmine\nfour\nfive\n";
+        let theirs = "one\ntwo\nyours\nfour\nfive\n";
+        let merged = merge(BASE, ours, theirs, "ours", "theirs", true);
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(merged.content, theirs);
+    }
+
+    #[test]
+    fn side_without_final_newline() {
+        let base = "one\ntwo";
+        let merged = merge(base, "ONE\ntwo", "one\ntwo", "ours", "theirs", false);
+        assert_eq!(merged.conflicts, 0);
+        assert_eq!(merged.content, "ONE\ntwo");
+
+        let merged = merge(base, "one\nmine", "one\nyours\n", "ours", "theirs", false);
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(
+            merged.content,
+            "one\n<<<<<<< ours\nmine\n=======\nyours\n>>>>>>> theirs\n"
+        );
+    }
+}
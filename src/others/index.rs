@@ -2,7 +2,7 @@ use anyhow::{Context, Result};
 
 use std::fs::{self};
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 use std::os::unix::fs::PermissionsExt;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -15,6 +15,7 @@ pub struct ObjectInfo {
     pub path: PathBuf, // path to the file
     pub size: u64,     //size of the file
     pub permissions: u32,
+    pub stage: u8, // 0 once merged, during a conflict 1 is the base, 2 ours and 3 theirs
 }
 #[derive(Debug)]
 pub struct Index {
@@ -49,10 +50,24 @@ impl ObjectInfo {
             path: path.to_path_buf(),
             size,
             permissions,
+            stage: 0,
         })
     }
+    // Entries coming from a tree only know their mode and hash.
+    pub fn from_tree_entry(path: &Path, hash: &str, mode: u32) -> Self {
+        Self {
+            obj_type: "blob".to_string(),
+            hash: hash.to_string(),
+            ctime: 0,
+            mtime: 0,
+            path: path.to_path_buf(),
+            size: 0,
+            permissions: mode,
+            stage: 0,
+        }
+    }
     pub fn pretty_print(&self) -> String {
-        format!(
+        let line = format!(
             "{} {} {} {} {} {} {}",
             self.permissions,
             self.hash,
@@ -61,11 +76,17 @@ impl ObjectInfo {
             self.size,
             self.ctime,
             self.mtime
-        )
+        );
+        // merged entries keep the old format without a stage column
+        if self.stage == 0 {
+            line
+        } else {
+            format!("{} {}", line, self.stage)
+        }
     }
     pub fn from_pretty_print(line: &str) -> Result<Self> {
         let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() < 5 {
+        if parts.len() < 7 {
             return Err(anyhow::anyhow!("Malformed index line: {}", line));
         }
         Ok(Self {
@@ -76,17 +97,52 @@ impl ObjectInfo {
             mtime: parts[6].trim().parse().context("Failed to parse mtime")?,
             path: PathBuf::from(parts[3]),
             size: parts[4].parse().context("Failed to parse size")?,
+            stage: match parts.get(7) {
+                Some(stage) => stage.parse().context("Failed to parse stage")?,
+                None => 0,
+            },
         })
     }
 }
 
+// Index paths are always relative to the repository root, without "./".
+pub fn normalize_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
 impl Index {
     pub fn new() -> Self {
         Index { obj: Vec::new() }
     }
+    pub fn load() -> Result<Self> {
+        let index_path = Path::new(".vcs").join("index");
+        if !index_path.exists() {
+            return Err(anyhow::anyhow!("The repository is not initialized"));
+        }
+        let content = fs::read_to_string(index_path).context("Failed to read the index file")?;
+        let mut index = Index::new();
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            index.add_object(ObjectInfo::from_pretty_print(line)?);
+        }
+        Ok(index)
+    }
     pub fn add_object(&mut self, object: ObjectInfo) {
         self.obj.push(object);
     }
+    // Paths that still have conflict stages, each listed once.
+    pub fn unmerged_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self
+            .obj
+            .iter()
+            .filter(|obj| obj.stage != 0)
+            .map(|obj| obj.path.clone())
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
 
     pub fn save_index_file_append(&self) -> Result<()> {
         let vcs_index_file = Path::new(".vcs");
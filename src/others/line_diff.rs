@@ -0,0 +1,549 @@
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/*
+ * Line based diff algorithms. All of them return the same edit script, one
+ * Edit per line of the old and the new side in order, and only differ in which
+ * of the equally short scripts they pick:
+ *     myers      the classic O(ND) shortest edit script, linear space variant
+ *     patience   anchors on lines that are unique on both sides first, which keeps
+ *                moved blocks and braces from being matched against each other
+ *     histogram  anchors on the rarest common line, like git's default for merges
+ * Patience and histogram fall back to myers when they find no anchor.
+ * */
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    Myers,
+    Patience,
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "myers" => Ok(DiffAlgorithm::Myers),
+            "patience" => Ok(DiffAlgorithm::Patience),
+            "histogram" => Ok(DiffAlgorithm::Histogram),
+            _ => Err(anyhow!("Unknown diff algorithm: {}", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// histogram gives up on lines that occur more often than this
+const MAX_CHAIN: usize = 64;
+
+struct Differ {
+    a: Vec<u32>,
+    b: Vec<u32>,
+    algorithm: DiffAlgorithm,
+    edits: Vec<Edit>,
+}
+
+pub fn diff<T: Hash + Eq>(a: &[T], b: &[T], algorithm: DiffAlgorithm) -> Vec<Edit> {
+    // lines are compared through small ids, hashing every line once
+    let mut ids: HashMap<&T, u32> = HashMap::new();
+    let mut intern = |line| {
+        let next = ids.len() as u32;
+        *ids.entry(line).or_insert(next)
+    };
+    let a: Vec<u32> = a.iter().map(&mut intern).collect();
+    let b: Vec<u32> = b.iter().map(&mut intern).collect();
+
+    let mut differ = Differ {
+        edits: Vec::with_capacity(a.len() + b.len()),
+        a,
+        b,
+        algorithm,
+    };
+    let (n, m) = (differ.a.len(), differ.b.len());
+    differ.diff_range(0, n, 0, m);
+    differ.edits
+}
+
+impl Differ {
+    fn push(&mut self, edit: Edit, count: usize) {
+        self.edits.extend(std::iter::repeat_n(edit, count));
+    }
+
+    fn diff_range(&mut self, a_lo: usize, a_hi: usize, b_lo: usize, b_hi: usize) {
+        let algorithm = self.algorithm;
+        self.diff_with(algorithm, a_lo, a_hi, b_lo, b_hi);
+    }
+
+    fn diff_with(
+        &mut self,
+        algorithm: DiffAlgorithm,
+        mut a_lo: usize,
+        mut a_hi: usize,
+        mut b_lo: usize,
+        mut b_hi: usize,
+    ) {
+        // the common prefix and suffix never need a search
+        let mut prefix = 0;
+        while a_lo < a_hi && b_lo < b_hi && self.a[a_lo] == self.b[b_lo] {
+            a_lo += 1;
+            b_lo += 1;
+            prefix += 1;
+        }
+        let mut suffix = 0;
+        while a_lo < a_hi && b_lo < b_hi && self.a[a_hi - 1] == self.b[b_hi - 1] {
+            a_hi -= 1;
+            b_hi -= 1;
+            suffix += 1;
+        }
+        self.push(Edit::Equal, prefix);
+
+        if a_lo == a_hi || b_lo == b_hi {
+            self.push(Edit::Delete, a_hi - a_lo);
+            self.push(Edit::Insert, b_hi - b_lo);
+        } else {
+            let anchored = match algorithm {
+                DiffAlgorithm::Myers => false,
+                DiffAlgorithm::Patience => self.patience(a_lo, a_hi, b_lo, b_hi),
+                DiffAlgorithm::Histogram => self.histogram(a_lo, a_hi, b_lo, b_hi),
+            };
+            if !anchored {
+                self.myers(a_lo, a_hi, b_lo, b_hi);
+            }
+        }
+
+        self.push(Edit::Equal, suffix);
+    }
+
+    /*
+     * Finds the middle snake by running the search forwards from the top left
+     * and backwards from the bottom right until the two paths overlap, then
+     * recurses on both halves. Needs O(N + M) memory instead of O(ND).
+     * */
+    fn myers(&mut self, a_lo: usize, a_hi: usize, b_lo: usize, b_hi: usize) {
+        let a = &self.a[a_lo..a_hi];
+        let b = &self.b[b_lo..b_hi];
+        // the search is at its slowest when the sides share nothing, so check first
+        let in_a: HashSet<u32> = a.iter().copied().collect();
+        if !b.iter().any(|line| in_a.contains(line)) {
+            self.push(Edit::Delete, a_hi - a_lo);
+            self.push(Edit::Insert, b_hi - b_lo);
+            return;
+        }
+        let (n, m) = (a.len() as isize, b.len() as isize);
+        let max_d = (n + m + 1) / 2;
+        let offset = max_d;
+        let len = 2 * max_d + 2;
+        let mut forward = vec![-1isize; len as usize];
+        let mut backward = vec![-1isize; len as usize];
+        forward[offset as usize + 1] = 0;
+        backward[offset as usize + 1] = 0;
+        let delta = n - m;
+        // with an odd delta the forward path is the one that can overlap first
+        let front = delta % 2 != 0;
+        let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+
+        for d in 0..max_d {
+            let mut k1 = -d + k1_start;
+            while k1 <= d - k1_end {
+                let k1_offset = (offset + k1) as usize;
+                let mut x1 =
+                    if k1 == -d || (k1 != d && forward[k1_offset - 1] < forward[k1_offset + 1]) {
+                        forward[k1_offset + 1]
+                    } else {
+                        forward[k1_offset - 1] + 1
+                    };
+                let mut y1 = x1 - k1;
+                while x1 < n && y1 < m && a[x1 as usize] == b[y1 as usize] {
+                    x1 += 1;
+                    y1 += 1;
+                }
+                forward[k1_offset] = x1;
+                if x1 > n {
+                    k1_end += 2;
+                } else if y1 > m {
+                    k1_start += 2;
+                } else if front {
+                    let k2_offset = offset + delta - k1;
+                    if k2_offset >= 0 && k2_offset < len && backward[k2_offset as usize] != -1 {
+                        let x2 = n - backward[k2_offset as usize];
+                        if x1 >= x2 {
+                            return self.split(a_lo, a_hi, b_lo, b_hi, x1 as usize, y1 as usize);
+                        }
+                    }
+                }
+                k1 += 2;
+            }
+
+            let mut k2 = -d + k2_start;
+            while k2 <= d - k2_end {
+                let k2_offset = (offset + k2) as usize;
+                let mut x2 =
+                    if k2 == -d || (k2 != d && backward[k2_offset - 1] < backward[k2_offset + 1]) {
+                        backward[k2_offset + 1]
+                    } else {
+                        backward[k2_offset - 1] + 1
+                    };
+                let mut y2 = x2 - k2;
+                while x2 < n && y2 < m && a[(n - x2 - 1) as usize] == b[(m - y2 - 1) as usize] {
+                    x2 += 1;
+                    y2 += 1;
+                }
+                backward[k2_offset] = x2;
+                if x2 > n {
+                    k2_end += 2;
+                } else if y2 > m {
+                    k2_start += 2;
+                } else if !front {
+                    let k1_offset = offset + delta - k2;
+                    if k1_offset >= 0 && k1_offset < len && forward[k1_offset as usize] != -1 {
+                        let x1 = forward[k1_offset as usize];
+                        let y1 = offset + x1 - k1_offset;
+                        if x1 >= n - x2 {
+                            return self.split(a_lo, a_hi, b_lo, b_hi, x1 as usize, y1 as usize);
+                        }
+                    }
+                }
+                k2 += 2;
+            }
+        }
+
+        // nothing in common
+        self.push(Edit::Delete, a_hi - a_lo);
+        self.push(Edit::Insert, b_hi - b_lo);
+    }
+
+    fn split(&mut self, a_lo: usize, a_hi: usize, b_lo: usize, b_hi: usize, x: usize, y: usize) {
+        self.diff_with(DiffAlgorithm::Myers, a_lo, a_lo + x, b_lo, b_lo + y);
+        self.diff_with(DiffAlgorithm::Myers, a_lo + x, a_hi, b_lo + y, b_hi);
+    }
+
+    // Diffs the gaps between matching (a, b) lines and emits the matches themselves.
+    fn between_anchors(
+        &mut self,
+        anchors: &[(usize, usize, usize)],
+        a_lo: usize,
+        a_hi: usize,
+        b_lo: usize,
+        b_hi: usize,
+    ) {
+        let (mut a_pos, mut b_pos) = (a_lo, b_lo);
+        for &(a_start, b_start, len) in anchors {
+            self.diff_range(a_pos, a_start, b_pos, b_start);
+            self.push(Edit::Equal, len);
+            a_pos = a_start + len;
+            b_pos = b_start + len;
+        }
+        self.diff_range(a_pos, a_hi, b_pos, b_hi);
+    }
+
+    /*
+     * Lines that occur exactly once on each side are matched up, the longest
+     * increasing subsequence of their positions in b gives the anchors.
+     * */
+    fn patience(&mut self, a_lo: usize, a_hi: usize, b_lo: usize, b_hi: usize) -> bool {
+        let mut counts: HashMap<u32, (usize, usize, usize)> = HashMap::new();
+        for i in a_lo..a_hi {
+            let entry = counts.entry(self.a[i]).or_insert((0, 0, 0));
+            entry.0 += 1;
+            entry.2 = i;
+        }
+        let mut unique = Vec::new();
+        for j in b_lo..b_hi {
+            if let Some(entry) = counts.get_mut(&self.b[j]) {
+                entry.1 += 1;
+            }
+        }
+        for j in b_lo..b_hi {
+            if let Some(&(1, 1, i)) = counts.get(&self.b[j]) {
+                unique.push((i, j));
+            }
+        }
+        if unique.is_empty() {
+            return false;
+        }
+        unique.sort();
+
+        // patience sorting: piles hold the index of their top card
+        let mut piles: Vec<usize> = Vec::new();
+        let mut previous: Vec<Option<usize>> = vec![None; unique.len()];
+        for (card, &(_, j)) in unique.iter().enumerate() {
+            let pile = piles.partition_point(|&top| unique[top].1 < j);
+            if pile > 0 {
+                previous[card] = Some(piles[pile - 1]);
+            }
+            if pile == piles.len() {
+                piles.push(card);
+            } else {
+                piles[pile] = card;
+            }
+        }
+        let mut anchors = Vec::new();
+        let mut card = piles.last().copied();
+        while let Some(current) = card {
+            let (i, j) = unique[current];
+            anchors.push((i, j, 1));
+            card = previous[current];
+        }
+        anchors.reverse();
+
+        self.between_anchors(&anchors, a_lo, a_hi, b_lo, b_hi);
+        true
+    }
+
+    /*
+     * Picks the common line that occurs the least often in a, grows the match
+     * around it as far as both sides agree and recurses on what is left.
+     * */
+    fn histogram(&mut self, a_lo: usize, a_hi: usize, b_lo: usize, b_hi: usize) -> bool {
+        let mut positions: HashMap<u32, Vec<usize>> = HashMap::new();
+        for i in a_lo..a_hi {
+            positions.entry(self.a[i]).or_default().push(i);
+        }
+
+        // (occurrences, length, a start, b start)
+        let mut best: Option<(usize, usize, usize, usize)> = None;
+        let mut j = b_lo;
+        while j < b_hi {
+            let Some(candidates) = positions.get(&self.b[j]) else {
+                j += 1;
+                continue;
+            };
+            let count = candidates.len();
+            if count > MAX_CHAIN || best.is_some_and(|(best_count, ..)| count > best_count) {
+                j += 1;
+                continue;
+            }
+            let mut next_j = j + 1;
+            for &i in candidates {
+                let (mut a_start, mut b_start) = (i, j);
+                while a_start > a_lo && b_start > b_lo && self.a[a_start - 1] == self.b[b_start - 1]
+                {
+                    a_start -= 1;
+                    b_start -= 1;
+                }
+                let (mut a_end, mut b_end) = (i + 1, j + 1);
+                while a_end < a_hi && b_end < b_hi && self.a[a_end] == self.b[b_end] {
+                    a_end += 1;
+                    b_end += 1;
+                }
+                let len = a_end - a_start;
+                let better = match best {
+                    None => true,
+                    Some((best_count, best_len, ..)) => {
+                        count < best_count || (count == best_count && len > best_len)
+                    }
+                };
+                if better {
+                    best = Some((count, len, a_start, b_start));
+                }
+                next_j = next_j.max(b_end);
+            }
+            j = next_j;
+        }
+
+        match best {
+            Some((_, len, a_start, b_start)) => {
+                self.between_anchors(&[(a_start, b_start, len)], a_lo, a_hi, b_lo, b_hi);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/*
+ * One "@@ -old_start,old_len +new_start,new_len @@" block. Lines hold the edit
+ * and the index of the line on the side it comes from, the old side for
+ * Equal and Delete, the new side for Insert.
+ * */
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    pub lines: Vec<(Edit, usize)>,
+}
+
+impl Hunk {
+    pub fn header(&self) -> String {
+        // like diff -u: the start is 1 based, an empty side names the line before it
+        let range = |start: usize, len: usize| match len {
+            0 => format!("{},0", start),
+            1 => format!("{}", start + 1),
+            _ => format!("{},{}", start + 1, len),
+        };
+        format!(
+            "@@ -{} +{} @@",
+            range(self.old_start, self.old_len),
+            range(self.new_start, self.new_len)
+        )
+    }
+}
+
+// Groups the edits into hunks, keeping `context` unchanged lines around every change.
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    // old and new line numbers before every edit
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for edit in edits {
+        positions.push((i, j));
+        match edit {
+            Edit::Equal => {
+                i += 1;
+                j += 1;
+            }
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+    positions.push((i, j));
+
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&k| edits[k] != Edit::Equal)
+        .collect();
+    let mut hunks = Vec::new();
+    let mut k = 0;
+    while k < changes.len() {
+        let start = changes[k].saturating_sub(context);
+        let mut last = changes[k];
+        // changes closer than two contexts apart share one hunk
+        while k + 1 < changes.len() && changes[k + 1] - last <= 2 * context + 1 {
+            k += 1;
+            last = changes[k];
+        }
+        let end = (last + context + 1).min(edits.len());
+        k += 1;
+
+        // within a run of changes the deletions come first, like diff -u
+        let mut lines = Vec::new();
+        let mut inserts = Vec::new();
+        for (edit, &(old, new)) in edits[start..end].iter().zip(&positions[start..end]) {
+            match edit {
+                Edit::Equal => {
+                    lines.append(&mut inserts);
+                    lines.push((Edit::Equal, old));
+                }
+                Edit::Delete => lines.push((Edit::Delete, old)),
+                Edit::Insert => inserts.push((Edit::Insert, new)),
+            }
+        }
+        lines.append(&mut inserts);
+
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        hunks.push(Hunk {
+            old_start,
+            old_len: old_end - old_start,
+            new_start,
+            new_len: new_end - new_start,
+            lines,
+        });
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [DiffAlgorithm; 3] = [
+        DiffAlgorithm::Myers,
+        DiffAlgorithm::Patience,
+        DiffAlgorithm::Histogram,
+    ];
+
+    // Replays the edits, checking they turn a into b.
+    fn replay<'a>(a: &[&'a str], b: &[&'a str], edits: &[Edit]) -> Vec<&'a str> {
+        let (mut i, mut j) = (0, 0);
+        let mut out = Vec::new();
+        for edit in edits {
+            match edit {
+                Edit::Equal => {
+                    assert_eq!(a[i], b[j]);
+                    out.push(a[i]);
+                    i += 1;
+                    j += 1;
+                }
+                Edit::Delete => i += 1,
+                Edit::Insert => {
+                    out.push(b[j]);
+                    j += 1;
+                }
+            }
+        }
+        assert_eq!((i, j), (a.len(), b.len()));
+        out
+    }
+
+    fn changes(edits: &[Edit]) -> usize {
+        edits.iter().filter(|edit| **edit != Edit::Equal).count()
+    }
+
+    #[test]
+    fn minimal_edit_script() {
+        let a = ["x", "a", "b", "c", "y"];
+        let b = ["x", "a", "c", "y", "z"];
+        for algorithm in ALGORITHMS {
+            let edits = diff(&a, &b, algorithm);
+            assert_eq!(replay(&a, &b, &edits), b);
+            assert_eq!(
+                edits,
+                [
+                    Edit::Equal,
+                    Edit::Equal,
+                    Edit::Delete,
+                    Edit::Equal,
+                    Edit::Equal,
+                    Edit::Insert
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn myers_finds_the_shortest_script() {
+        let a = ["a", "b", "c", "a", "b", "b", "a"];
+        let b = ["c", "b", "a", "b", "a", "c"];
+        let edits = diff(&a, &b, DiffAlgorithm::Myers);
+        assert_eq!(replay(&a, &b, &edits), b);
+        assert_eq!(changes(&edits), 5);
+    }
+
+    #[test]
+    fn identical_and_empty_sides() {
+        let a = ["a", "b"];
+        let none: [&str; 0] = [];
+        for algorithm in ALGORITHMS {
+            assert_eq!(diff(&a, &a, algorithm), [Edit::Equal, Edit::Equal]);
+            assert_eq!(diff(&none, &a, algorithm), [Edit::Insert, Edit::Insert]);
+            assert_eq!(diff(&a, &none, algorithm), [Edit::Delete, Edit::Delete]);
+            assert!(diff(&none, &none, algorithm).is_empty());
+        }
+    }
+
+    #[test]
+    fn missing_final_newline_changes_the_last_line() {
+        let a: Vec<&str> = "one\ntwo".split_inclusive('\n').collect();
+        let b: Vec<&str> = "one\ntwo\n".split_inclusive('\n').collect();
+        for algorithm in ALGORITHMS {
+            assert_eq!(
+                diff(&a, &b, algorithm),
+                [Edit::Equal, Edit::Delete, Edit::Insert]
+            );
+        }
+    }
+
+    #[test]
+    fn hunk_header_and_context() {
+        let a = ["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+        let b = ["1", "2", "3", "4", "five", "6", "7", "8", "9"];
+        let hunks = hunks(&diff(&a, &b, DiffAlgorithm::Myers), 3);
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].header(), "@@ -2,7 +2,7 @@");
+    }
+}
@@ -0,0 +1,76 @@
+use crate::objects::commit::Commit;
+use crate::objects::store::{self, ObjectKind};
+use crate::objects::tag::Tag;
+use crate::objects::tree::Tree;
+use crate::others::{index, reflog, refs};
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/*
+ * Everything that must survive a gc: the commit in HEAD, every ref under
+ * .vcs/refs and the blobs staged in the index that were not committed yet.
+ * */
+pub fn roots() -> Result<Vec<String>> {
+    let mut roots = Vec::new();
+    let head = fs::read_to_string(Path::new(".vcs").join("HEAD"))?
+        .trim()
+        .to_string();
+    if !head.starts_with("refs/") && !head.is_empty() {
+        roots.push(head);
+    }
+    for (_, hash) in refs::list_refs()? {
+        roots.push(hash);
+    }
+    // old tips stay around as long as the logs mention them
+    roots.extend(reflog::all_hashes()?);
+    // an unfinished merge still needs both of its sides
+    for state in ["MERGE_HEAD", "ORIG_HEAD"] {
+        let state = Path::new(".vcs").join(state);
+        if state.exists() {
+            roots.push(fs::read_to_string(state)?.trim().to_string());
+        }
+    }
+    for obj in index::Index::load()?.obj {
+        roots.push(obj.hash);
+    }
+    Ok(roots)
+}
+
+pub fn reachable_objects(roots: &[String]) -> Result<HashSet<String>> {
+    let mut reachable = HashSet::new();
+    let mut to_visit: Vec<String> = roots.to_vec();
+
+    while let Some(hash) = to_visit.pop() {
+        if !reachable.insert(hash.clone()) {
+            continue;
+        }
+        let (kind, _) =
+            store::read_object(&hash).with_context(|| format!("Cannot walk object {}", hash))?;
+        match kind {
+            ObjectKind::Commit => {
+                let commit = Commit::read(&hash)?;
+                to_visit.push(commit.tree);
+                to_visit.extend(commit.parents);
+            }
+            ObjectKind::Tree => {
+                for entry in Tree::read(&hash)? {
+                    // blobs have no children, checking that they exist is enough
+                    if entry.kind == ObjectKind::Blob {
+                        if !store::object_exists(&entry.hash) {
+                            return Err(anyhow!("Cannot walk object {}: missing blob", entry.hash));
+                        }
+                        reachable.insert(entry.hash);
+                    } else {
+                        to_visit.push(entry.hash);
+                    }
+                }
+            }
+            ObjectKind::Tag => to_visit.push(Tag::read(&hash)?.object),
+            ObjectKind::Blob => {}
+        }
+    }
+
+    Ok(reachable)
+}
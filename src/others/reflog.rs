@@ -0,0 +1,152 @@
+use crate::objects::commit::Signature;
+use crate::others::refs;
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/*
+ * Every update of a ref is appended to .vcs/logs/<ref>, HEAD included, one line
+ * per update like git:
+ *     <old hash> <new hash> Jane Doe <jane@example.com> 1700000000 +0200\t<reason>
+ * A ref that did not exist before is logged with an old hash of zeros.
+ * */
+
+pub const ZERO_HASH: &str = "0000000000000000000000000000000000000000";
+
+pub struct Entry {
+    pub old: String,
+    pub new: String,
+    pub who: Signature,
+    pub reason: String,
+}
+
+pub fn logs_dir() -> PathBuf {
+    Path::new(".vcs").join("logs")
+}
+
+/*
+ * The ref whose log a short name stands for: an empty name is HEAD, a name
+ * under .vcs/refs such as "stash" is that ref, any other is a branch.
+ * */
+pub fn log_name(name: &str) -> Result<String> {
+    Ok(match name {
+        "" | "HEAD" => String::from("HEAD"),
+        name if name.starts_with("refs/") => name.to_string(),
+        name if refs::read_ref(&format!("refs/{}", name))?.is_some() => format!("refs/{}", name),
+        name => format!("refs/heads/{}", name),
+    })
+}
+
+fn log_path(name: &str) -> PathBuf {
+    logs_dir().join(name)
+}
+
+pub fn append(name: &str, old: Option<&str>, new: &str, reason: &str) -> Result<()> {
+    let path = log_path(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let line = format!(
+        "{} {} {}\t{}\n",
+        old.unwrap_or(ZERO_HASH),
+        new,
+        Signature::for_reflog().pretty_print(),
+        reason.lines().next().unwrap_or("")
+    );
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open the log of {}", name))?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
+
+// The entries of a ref, oldest first, empty when it was never logged.
+pub fn read(name: &str) -> Result<Vec<Entry>> {
+    let path = log_path(name);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for line in fs::read_to_string(path)?.lines() {
+        let malformed = || anyhow!("Malformed log entry of {}: {}", name, line);
+        let (head, reason) = line.split_once('\t').unwrap_or((line, ""));
+        let mut parts = head.splitn(3, ' ');
+        let (Some(old), Some(new), Some(who)) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(malformed());
+        };
+        entries.push(Entry {
+            old: old.to_string(),
+            new: new.to_string(),
+            who: Signature::from_pretty_print(who).map_err(|_| malformed())?,
+            reason: reason.to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+// The log follows a renamed branch.
+pub fn rename(old_name: &str, new_name: &str) -> Result<()> {
+    let (from, to) = (log_path(old_name), log_path(new_name));
+    if !from.is_file() {
+        return Ok(());
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)?;
+    Ok(())
+}
+
+// Forgets the entry that <name>@{n} stands for, the stash list is kept that way.
+pub fn drop_entry(name: &str, n: usize) -> Result<()> {
+    let path = log_path(name);
+    let mut lines: Vec<String> = match fs::read_to_string(&path) {
+        Ok(content) => content.lines().map(str::to_string).collect(),
+        Err(_) => Vec::new(),
+    };
+    let i = lines
+        .len()
+        .checked_sub(n + 1)
+        .ok_or_else(|| anyhow!("The log of '{}' only has {} entries", name, lines.len()))?;
+    lines.remove(i);
+    let content: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+    fs::write(&path, content).with_context(|| format!("Failed to write the log of {}", name))
+}
+
+pub fn delete(name: &str) -> Result<()> {
+    let path = log_path(name);
+    if path.is_file() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+// Every hash the logs mention, they keep old tips alive through gc.
+pub fn all_hashes() -> Result<Vec<String>> {
+    let mut hashes = Vec::new();
+    let mut to_visit = vec![logs_dir()];
+    while let Some(dir) = to_visit.pop() {
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                to_visit.push(path);
+                continue;
+            }
+            let name = path.strip_prefix(logs_dir())?.to_string_lossy().to_string();
+            for entry in read(&name)? {
+                hashes.extend(
+                    [entry.old, entry.new]
+                        .into_iter()
+                        .filter(|hash| hash != ZERO_HASH),
+                );
+            }
+        }
+    }
+    Ok(hashes)
+}
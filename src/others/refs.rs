@@ -0,0 +1,124 @@
+use crate::others::reflog;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub fn refs_dir() -> PathBuf {
+    Path::new(".vcs").join("refs")
+}
+
+// Every ref under .vcs/refs with the commit it points to, empty refs are skipped.
+pub fn list_refs() -> Result<Vec<(String, String)>> {
+    let mut refs = Vec::new();
+    if refs_dir().exists() {
+        collect_refs(&refs_dir(), "refs", &mut refs)?;
+    }
+    refs.sort();
+    Ok(refs)
+}
+
+fn collect_refs(dir: &Path, prefix: &str, refs: &mut Vec<(String, String)>) -> Result<()> {
+    for entry in fs::read_dir(dir).context("Failed to read refs directory")? {
+        let entry = entry?;
+        let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        if entry.path().is_dir() {
+            collect_refs(&entry.path(), &name, refs)?;
+        } else {
+            let hash = fs::read_to_string(entry.path())?.trim().to_string();
+            if !hash.is_empty() {
+                refs.push((name, hash));
+            }
+        }
+    }
+    Ok(())
+}
+
+// The commit a full ref name such as "refs/heads/main" points to, None when it does not exist.
+pub fn read_ref(name: &str) -> Result<Option<String>> {
+    let path = Path::new(".vcs").join(name);
+    if !name.starts_with("refs/") || !path.is_file() {
+        return Ok(None);
+    }
+    let hash = fs::read_to_string(path)?.trim().to_string();
+    Ok(if hash.is_empty() { None } else { Some(hash) })
+}
+
+/*
+ * Branch and tag names follow git's rules, a name is refused when it
+ *     is empty, "@", starts with "-" or ends with "/", "." or ".lock"
+ *     has a component starting with "." or contains "..", "//" or "@{"
+ *     contains a space, a control character or one of ~ ^ : ? * [ \
+ *     is "HEAD" or 40 hex digits, which would read as HEAD or a commit
+ * */
+pub fn check_ref_name(name: &str) -> Result<()> {
+    let invalid = name.is_empty()
+        || name == "@"
+        || name == "HEAD"
+        || (name.len() == 40 && name.chars().all(|c| c.is_ascii_hexdigit()))
+        || name.starts_with('-')
+        || name.ends_with('/')
+        || name.ends_with('.')
+        || name.ends_with(".lock")
+        || name.split('/').any(|part| part.starts_with('.'))
+        || ["..", "//", "@{"].iter().any(|bad| name.contains(bad))
+        || name
+            .chars()
+            .any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c));
+    if invalid {
+        return Err(anyhow::anyhow!("'{}' is not a valid ref name", name));
+    }
+    Ok(())
+}
+
+/*
+ * An existing ref that leaves no room for the name: refs are files, so
+ * "refs/heads/x" and "refs/heads/x/y" cannot both exist.
+ * */
+pub fn clashing_ref(name: &str) -> Result<Option<String>> {
+    Ok(list_refs()?
+        .into_iter()
+        .map(|(existing, _)| existing)
+        .find(|existing| {
+            name.starts_with(&format!("{}/", existing))
+                || existing.starts_with(&format!("{}/", name))
+        }))
+}
+
+// Points a full ref name at a commit, creating the directories of nested names.
+pub fn write_ref(name: &str, hash: &str) -> Result<()> {
+    let path = Path::new(".vcs").join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, hash).with_context(|| format!("Failed to update {}", name))
+}
+
+/*
+ * Moves a ref and logs the update. When HEAD is on the branch, the update is
+ * logged for HEAD as well.
+ * */
+pub fn update_ref(name: &str, hash: &str, reason: &str) -> Result<()> {
+    let old = read_ref(name)?;
+    write_ref(name, hash)?;
+    reflog::append(name, old.as_deref(), hash, reason)?;
+    let head = fs::read_to_string(Path::new(".vcs").join("HEAD"))?;
+    if head.trim() == name {
+        reflog::append("HEAD", old.as_deref(), hash, reason)?;
+    }
+    Ok(())
+}
+
+// Removes a ref and the directories of nested names it leaves empty, refs/heads and such stay.
+pub fn delete_ref(name: &str) -> Result<()> {
+    let path = Path::new(".vcs").join(name);
+    fs::remove_file(&path).with_context(|| format!("Failed to delete {}", name))?;
+    let mut dir = path.parent();
+    while let Some(parent) = dir {
+        if parent.parent() == Some(refs_dir().as_path()) || fs::read_dir(parent)?.next().is_some() {
+            break;
+        }
+        fs::remove_dir(parent)?;
+        dir = parent.parent();
+    }
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use crate::objects::commit::Commit;
+use crate::objects::store::{self, ObjectKind};
+use crate::objects::tag::Tag;
+use crate::others::{file_altering, reflog, refs};
+use anyhow::{anyhow, Context, Result};
+
+/*
+ * Revision expressions, a subset of what git accepts:
+ *     HEAD, main, refs/heads/main   a ref, looked up as given, under refs/,
+ *                                   refs/tags/ and refs/heads/
+ *     3f2a9c1                       a hash, abbreviated to at least 4 characters
+ *     <rev>~<n>                     the n-th first-parent ancestor, ~ alone is ~1
+ *     <rev>^<n>                     the n-th parent of a merge, ^ alone is ^1, ^0 is <rev>
+ *     v1.0                          a tag, annotated tags stand for the commit they tag
+ *                                   wherever a commit or a tree is expected
+ *     HEAD@{n}, main@{n}            where HEAD or a branch was n updates ago, from its log
+ *     stash@{n}                     the n-th most recent stash
+ *     <rev1>..<rev2>                commits reachable from rev2 but not from rev1,
+ *                                   a missing side means HEAD
+ * Suffixes can be chained: main~2^2~1.
+ * */
+
+const MIN_ABBREV: usize = 4;
+
+fn is_hex(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn resolve_abbreviated(name: &str) -> Result<Option<String>> {
+    if name.len() < MIN_ABBREV || name.len() > 40 || !is_hex(name) {
+        return Ok(None);
+    }
+    let candidates = store::objects_with_prefix(name)?;
+    match candidates.len() {
+        0 => Ok(None),
+        1 => Ok(candidates.into_iter().next()),
+        _ => {
+            let mut message = format!("Short hash '{}' is ambiguous, candidates are:", name);
+            for hash in &candidates {
+                let kind = store::read_object(hash)
+                    .map(|(kind, _)| kind.to_string())
+                    .unwrap_or_else(|_| String::from("unknown"));
+                message.push_str(&format!("\n  {} {}", hash, kind));
+            }
+            Err(anyhow!(message))
+        }
+    }
+}
+
+/*
+ * "<ref>@{n}", where the ref was n updates ago according to its log. An empty
+ * ref is HEAD, "stash" is refs/stash and a branch name stands for refs/heads/<name>.
+ * */
+fn resolve_reflog(name: &str) -> Result<Option<String>> {
+    let Some((base, rest)) = name.split_once("@{") else {
+        return Ok(None);
+    };
+    let Some(count) = rest.strip_suffix('}') else {
+        return Ok(None);
+    };
+    let count: usize = count
+        .parse()
+        .with_context(|| format!("Invalid revision '{}'", name))?;
+    let log_name = reflog::log_name(base)?;
+    let entries = reflog::read(&log_name)?;
+    let entry = entries
+        .len()
+        .checked_sub(count + 1)
+        .map(|i| &entries[i])
+        .ok_or_else(|| {
+            anyhow!(
+                "The log of '{}' only has {} entries",
+                log_name,
+                entries.len()
+            )
+        })?;
+    Ok(Some(entry.new.clone()))
+}
+
+// The part of an expression before any ~ or ^ suffix.
+fn resolve_name(name: &str) -> Result<String> {
+    if let Some(hash) = resolve_reflog(name)? {
+        return Ok(hash);
+    }
+    if name == "HEAD" {
+        return file_altering::get_head_commit()?
+            .ok_or_else(|| anyhow!("HEAD does not point at a commit yet"));
+    }
+    if name.len() == 40 && is_hex(name) && store::object_exists(name) {
+        return Ok(name.to_ascii_lowercase());
+    }
+    if !name.is_empty() && !name.contains("..") {
+        for candidate in [
+            name.to_string(),
+            format!("refs/{}", name),
+            format!("refs/tags/{}", name),
+            format!("refs/heads/{}", name),
+        ] {
+            if let Some(hash) = refs::read_ref(&candidate)? {
+                return Ok(hash);
+            }
+        }
+    }
+    resolve_abbreviated(name)?.ok_or_else(|| anyhow!("Unknown revision '{}'", name))
+}
+
+fn parse_count(digits: &str, spec: &str) -> Result<usize> {
+    if digits.is_empty() {
+        return Ok(1);
+    }
+    digits
+        .parse()
+        .with_context(|| format!("Invalid revision '{}'", spec))
+}
+
+// Any object: a commit, an annotated tag, or whatever an abbreviated hash names.
+pub fn resolve_object(spec: &str) -> Result<String> {
+    let spec = spec.trim();
+    if spec.contains("..") {
+        return Err(anyhow!(
+            "'{}' is a range, a single revision is expected",
+            spec
+        ));
+    }
+    let split = spec.find(['~', '^']).unwrap_or(spec.len());
+    let (name, mut suffix) = spec.split_at(split);
+    let mut hash = resolve_name(name)?;
+
+    while let Some(op) = suffix.chars().next() {
+        if op != '~' && op != '^' {
+            return Err(anyhow!("Invalid revision '{}'", spec));
+        }
+        let rest = &suffix[1..];
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let count = parse_count(&rest[..end], spec)?;
+        suffix = &rest[end..];
+
+        let commit = Commit::read(&peel(&hash)?)
+            .with_context(|| format!("'{}' does not name a commit", spec))?;
+        if op == '~' {
+            let mut parents = commit.parents;
+            for _ in 0..count {
+                let parent = parents
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| anyhow!("Revision '{}' goes past the first commit", spec))?;
+                parents = Commit::read(&parent)?.parents;
+                hash = parent;
+            }
+        } else if count > 0 {
+            hash = commit
+                .parents
+                .get(count - 1)
+                .cloned()
+                .ok_or_else(|| anyhow!("Revision '{}' has no parent number {}", spec, count))?;
+        }
+    }
+    Ok(hash)
+}
+
+// Follows annotated tags down to the object they tag.
+pub fn peel(hash: &str) -> Result<String> {
+    let mut hash = hash.to_string();
+    while store::read_object(&hash)?.0 == ObjectKind::Tag {
+        hash = Tag::read(&hash)?.object;
+    }
+    Ok(hash)
+}
+
+pub fn resolve_commit(spec: &str) -> Result<String> {
+    let hash = peel(&resolve_object(spec)?)?;
+    let (kind, _) = store::read_object(&hash)?;
+    if kind != ObjectKind::Commit {
+        return Err(anyhow!("'{}' is a {}, not a commit", spec, kind));
+    }
+    Ok(hash)
+}
+
+// A tree, or the tree of a commit.
+pub fn resolve_tree(spec: &str) -> Result<String> {
+    let hash = peel(&resolve_object(spec)?)?;
+    match store::read_object(&hash)?.0 {
+        ObjectKind::Tree => Ok(hash),
+        ObjectKind::Commit => Ok(Commit::read(&hash)?.tree),
+        kind => Err(anyhow!("'{}' is a {}, not a tree", spec, kind)),
+    }
+}
+
+/*
+ * "<rev1>..<rev2>" gives (Some(rev1), rev2), the commits to hide and the commit
+ * to start from. A single revision gives (None, rev).
+ * */
+pub fn resolve_range(spec: &str) -> Result<(Option<String>, String)> {
+    match spec.split_once("..") {
+        Some((from, to)) => {
+            let from = if from.is_empty() { "HEAD" } else { from };
+            let to = if to.is_empty() { "HEAD" } else { to };
+            Ok((Some(resolve_commit(from)?), resolve_commit(to)?))
+        }
+        None => Ok((None, resolve_commit(spec)?)),
+    }
+}